@@ -1,158 +1,115 @@
 use anyhow::{Result, anyhow};
-use futures::stream::{AbortHandle, AbortRegistration, Abortable};
-use lunio_client::{Client, FileEntry};
+use futures::stream::{AbortHandle, Abortable};
+use lunio_client::{Client, FileEntry, ListOptions, RootEntry, RootOptions, SearchMode, ThumbnailBatch, ThumbnailPriority};
 use once_cell::sync::Lazy;
-use tokio::sync::{Mutex, mpsc, oneshot};
-
-static CLIENT_TX: Lazy<Mutex<Option<mpsc::Sender<ClientCmd>>>> = Lazy::new(|| Mutex::new(None));
-
-pub enum ClientCmd {
-    Search {
-        query: String,
-        limit: Option<usize>,
-        respond: oneshot::Sender<Result<Vec<FileEntry>>>,
-    },
-    ListDir {
-        path: String,
-        abort: AbortHandle,
-        abort_reg: AbortRegistration,
-        respond: oneshot::Sender<Result<Vec<FileEntry>>>,
-    },
-    RequestThumbnail {
-        id: String,
-        respond: oneshot::Sender<Result<()>>,
-    },
-    GetThumbnail {
-        id: String,
-        respond: oneshot::Sender<Result<Vec<u8>>>,
-    },
-    OpenFile {
-        path: String,
-        respond: oneshot::Sender<Result<()>>,
-    },
-    Shutdown {
-        respond: oneshot::Sender<Result<()>>,
-    }
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, ipc::Channel};
+use tokio::sync::Mutex;
+
+/// Name of the webview event daemon pushes are forwarded as.
+pub const FS_EVENT: &str = "fs-event";
+
+static CLIENT: Lazy<Mutex<Option<Client>>> = Lazy::new(|| Mutex::new(None));
+
+/// Parts of a directory listing, passed on to the webview as they arrive.
+#[derive(Clone, Serialize)]
+#[serde(tag = "event", content = "data", rename_all = "camelCase")]
+pub enum ListingEvent {
+    Started { total: usize },
+    Chunk { entries: Vec<FileEntry> }
 }
 
-pub async fn connect() -> Result<()> {
-    let (tx, mut rx) = mpsc::channel::<ClientCmd>(64);
-    let mut client = Client::connect().await?;
-
-    tokio::spawn(async move {
-        let mut current_list_abort: Option<AbortHandle> = None;
-
-        while let Some(cmd) = rx.recv().await {
-            match cmd {
-                ClientCmd::Search { query, limit, respond } => {
-                    let r = client.search(query, limit).await;
-                    let _ = respond.send(r);
-                }
-
-                ClientCmd::ListDir { path, respond, abort, abort_reg } => {
-                    if let Some(old) = current_list_abort.take() {
-                        old.abort();
-                    }
-
-                    current_list_abort = Some(abort);
-
-                    let future = client.list_dir(path);
-                    let abortable = Abortable::new(future, abort_reg);
-
-                    let r = match abortable.await {
-                        Ok(res) => res,
-                        Err(_) => Ok(vec![])
-                    };
-
-                    let _ = respond.send(r);
-                    current_list_abort = None;
-                }
-
-                ClientCmd::RequestThumbnail { id, respond } => {
-                    let r = client.request_thumbnail(id).await;
-                    let _ = respond.send(r);
-                }
-
-                ClientCmd::GetThumbnail { id, respond } => {
-                    let r = client.get_thumbnail(id).await;
-                    let _ = respond.send(r);
-                }
-
-                ClientCmd::OpenFile { path, respond } => {
-                    let r = client.open_file(path).await;
-                    let _ = respond.send(r);
-                }
-
-                ClientCmd::Shutdown { respond } => {
-                    let r = client.shutdown().await;
-                    let _ = respond.send(r);
-                    break;
-                }
+/// The directory listing in flight; opening another folder abandons it.
+static CURRENT_LIST: Lazy<Mutex<Option<AbortHandle>>> = Lazy::new(|| Mutex::new(None));
+
+pub async fn connect(app: AppHandle) -> Result<()> {
+    let client = Client::connect().await?;
+
+    if let Some(mut events) = client.events() {
+        tokio::spawn(async move {
+            while let Some(event) = events.recv().await {
+                let _ = app.emit(FS_EVENT, event);
             }
-        }
-    });
+        });
+    }
 
-    *CLIENT_TX.lock().await = Some(tx);
+    *CLIENT.lock().await = Some(client);
     Ok(())
 }
 
-async fn send<R>(
-    f: impl FnOnce(oneshot::Sender<Result<R>>) -> ClientCmd
-) -> Result<R> {
-    let tx = CLIENT_TX
+async fn client() -> Result<Client> {
+    CLIENT
         .lock().await
-        .as_ref()
-        .ok_or_else(|| anyhow!("Client not connected"))?
-        .clone();
+        .clone()
+        .ok_or_else(|| anyhow!("Client not connected"))
+}
 
-    let (res_tx, res_rx) = oneshot::channel();
+pub async fn search(query: String, limit: Option<usize>, mode: SearchMode) -> Result<Vec<FileEntry>> {
+    client().await?.search(query, limit, mode).await
+}
 
-    tx.send(f(res_tx))
-        .await
-        .map_err(|_| anyhow!("Client worker is offline"))?;
+pub async fn list_dir(path: String, options: ListOptions, on_event: Channel<ListingEvent>) -> Result<()> {
+    let client = client().await?;
+    let (abort, abort_reg) = AbortHandle::new_pair();
 
-    res_rx.await?
+    if let Some(old) = CURRENT_LIST.lock().await.replace(abort) {
+        old.abort();
+    }
+
+    let listing = async move {
+        let mut stream = client.list_dir_stream(path, options).await?;
+        on_event.send(ListingEvent::Started { total: stream.total })?;
+
+        while let Some(entries) = stream.next_chunk().await? {
+            on_event.send(ListingEvent::Chunk { entries })?;
+        }
+        Ok(())
+    };
+
+    match Abortable::new(listing, abort_reg).await {
+        Ok(res) => res,
+        Err(_) => Ok(())
+    }
 }
 
-async fn send_abortable<R>(
-    f: impl FnOnce(AbortHandle, AbortRegistration, oneshot::Sender<Result<R>>) -> ClientCmd
-) -> Result<R> {
-    let tx = CLIENT_TX
-        .lock().await
-        .as_ref()
-        .ok_or_else(|| anyhow!("Client not connected"))?
-        .clone();
+pub async fn request_thumbnail(id: String) -> Result<()> {
+    client().await?.request_thumbnail(id).await
+}
 
-    let (abort, abort_reg) = AbortHandle::new_pair();
-    let (res_tx, res_rx) = oneshot::channel();
+pub async fn get_thumbnail(id: String) -> Result<Vec<u8>> {
+    client().await?.get_thumbnail(id).await
+}
 
-    tx.send(f(abort, abort_reg, res_tx))
-        .await
-        .map_err(|_| anyhow!("Client worker is offline"))?;
+pub async fn get_thumbnails(ids: Vec<String>) -> Result<ThumbnailBatch> {
+    client().await?.get_thumbnails(ids).await
+}
 
-    res_rx.await?
+pub async fn request_thumbnails(ids: Vec<String>, priority: ThumbnailPriority) -> Result<ThumbnailBatch> {
+    client().await?.request_thumbnails(ids, priority).await
 }
 
-pub async fn search(query: String, limit: Option<usize>) -> Result<Vec<FileEntry>> {
-    send(|respond| ClientCmd::Search { query, limit, respond }).await
+pub async fn open_file(path: String) -> Result<()> {
+    client().await?.open_file(path).await
 }
 
-pub async fn list_dir(path: String) -> Result<Vec<FileEntry>> {
-    send_abortable(|abort, abort_reg, respond| ClientCmd::ListDir { path, abort, respond, abort_reg }).await
+pub async fn add_root(path: String, options: RootOptions) -> Result<RootEntry> {
+    client().await?.add_root(path, options).await
 }
 
-pub async fn request_thumbnail(id: String) -> Result<()> {
-    send(|respond| ClientCmd::RequestThumbnail { id, respond }).await
+pub async fn remove_root(path: String) -> Result<()> {
+    client().await?.remove_root(path).await
 }
 
-pub async fn get_thumbnail(id: String) -> Result<Vec<u8>> {
-    send(|respond| ClientCmd::GetThumbnail { id, respond }).await
+pub async fn list_roots() -> Result<Vec<RootEntry>> {
+    client().await?.list_roots().await
 }
 
-pub async fn open_file(path: String) -> Result<()> {
-    send(|respond| ClientCmd::OpenFile { path, respond }).await
+pub async fn subscribe(paths: Vec<String>) -> Result<()> {
+    client().await?.subscribe(paths).await
 }
 
 pub async fn shutdown() -> Result<()> {
-    send(|respond| ClientCmd::Shutdown { respond }).await
-}
\ No newline at end of file
+    let r = client().await?.shutdown().await;
+    *CLIENT.lock().await = None;
+    r
+}
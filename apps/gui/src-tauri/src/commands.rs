@@ -1,20 +1,24 @@
-use lunio_client::FileEntry;
+use lunio_client::{FileEntry, ListOptions, RootEntry, RootOptions, SearchMode, ThumbnailBatch, ThumbnailPriority, WatchMode};
 
-use crate::client;
+use tauri::ipc::Channel;
+
+use crate::client::{self, ListingEvent};
 
 #[tauri::command(async)]
-pub async fn cmd_connect() -> Result<(), String> {
-    client::connect().await.map_err(|e| e.to_string())
+pub async fn cmd_connect(app: tauri::AppHandle) -> Result<(), String> {
+    client::connect(app).await.map_err(|e| e.to_string())
 }
 
 #[tauri::command(async)]
-pub async fn cmd_search(query: String, limit: Option<usize>) -> Result<Vec<FileEntry>, String> {
-    client::search(query, limit).await.map_err(|e| e.to_string())
+pub async fn cmd_search(query: String, limit: Option<usize>, mode: Option<SearchMode>) -> Result<Vec<FileEntry>, String> {
+    client::search(query, limit, mode.unwrap_or_default()).await.map_err(|e| e.to_string())
 }
 
+/// Entries are sent through `on_event` a chunk at a time, in the order
+/// `options` asks for; the command returns once the listing is complete.
 #[tauri::command(async)]
-pub async fn cmd_list_dir(path: String) -> Result<Vec<FileEntry>, String> {
-    client::list_dir(path).await.map_err(|e| e.to_string())
+pub async fn cmd_list_dir(path: String, options: Option<ListOptions>, on_event: Channel<ListingEvent>) -> Result<(), String> {
+    client::list_dir(path, options.unwrap_or_default(), on_event).await.map_err(|e| e.to_string())
 }
 
 #[tauri::command(async)]
@@ -22,9 +26,49 @@ pub async fn cmd_request_thumbnail(id: String) -> Result<(), String> {
     client::request_thumbnail(id).await.map_err(|e| e.to_string())
 }
 
+/// Returned as raw bytes, which the webview receives as an `ArrayBuffer`.
+#[tauri::command(async)]
+pub async fn cmd_get_thumbnail(id: String) -> Result<tauri::ipc::Response, String> {
+    client::get_thumbnail(id).await
+        .map(tauri::ipc::Response::new)
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command(async)]
-pub async fn cmd_get_thumbnail(id: String) -> Result<Vec<u8>, String> {
-    client::get_thumbnail(id).await.map_err(|e| e.to_string())
+pub async fn cmd_get_thumbnails(ids: Vec<String>) -> Result<tauri::ipc::Response, String> {
+    client::get_thumbnails(ids).await
+        .map(pack_thumbnails)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command(async)]
+pub async fn cmd_request_thumbnails(ids: Vec<String>, priority: Option<ThumbnailPriority>) -> Result<tauri::ipc::Response, String> {
+    client::request_thumbnails(ids, priority.unwrap_or_default()).await
+        .map(pack_thumbnails)
+        .map_err(|e| e.to_string())
+}
+
+/// Lays a batch out the way the daemon sends it: the big-endian length of a
+/// JSON header, the header, then the ready thumbnails back to back.
+fn pack_thumbnails(batch: ThumbnailBatch) -> tauri::ipc::Response {
+    let ready: Vec<_> = batch.ready
+        .iter()
+        .map(|(id, bytes)| serde_json::json!({ "id": id, "len": bytes.len() }))
+        .collect();
+
+    let head = serde_json::to_vec(&serde_json::json!({
+        "ready": ready,
+        "pending": batch.pending,
+        "missing": batch.missing
+    })).unwrap();
+
+    let mut out = (head.len() as u32).to_be_bytes().to_vec();
+    out.extend_from_slice(&head);
+    for (_, bytes) in batch.ready {
+        out.extend_from_slice(&bytes);
+    }
+
+    tauri::ipc::Response::new(out)
 }
 
 #[tauri::command(async)]
@@ -32,6 +76,45 @@ pub async fn cmd_open_file(path: String) -> Result<(), String> {
     client::open_file(path).await.map_err(|e| e.to_string())
 }
 
+#[tauri::command(async)]
+pub async fn cmd_add_root(
+    path: String,
+    exclude: Option<Vec<String>>,
+    max_depth: Option<usize>,
+    watch: Option<bool>,
+    include_hidden: Option<bool>,
+    follow_links: Option<bool>,
+    watch_mode: Option<WatchMode>,
+    poll_interval_secs: Option<u64>
+) -> Result<RootEntry, String> {
+    let options = RootOptions {
+        exclude: exclude.unwrap_or_default(),
+        max_depth,
+        watch,
+        include_hidden,
+        follow_links,
+        watch_mode,
+        poll_interval_secs
+    };
+
+    client::add_root(path, options).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command(async)]
+pub async fn cmd_remove_root(path: String) -> Result<(), String> {
+    client::remove_root(path).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command(async)]
+pub async fn cmd_list_roots() -> Result<Vec<RootEntry>, String> {
+    client::list_roots().await.map_err(|e| e.to_string())
+}
+
+#[tauri::command(async)]
+pub async fn cmd_subscribe(paths: Vec<String>) -> Result<(), String> {
+    client::subscribe(paths).await.map_err(|e| e.to_string())
+}
+
 #[tauri::command(async)]
 pub async fn cmd_shutdown() -> Result<(), String> {
     client::shutdown().await.map_err(|e| e.to_string())
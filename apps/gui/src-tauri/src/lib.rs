@@ -36,7 +36,13 @@ pub fn run() {
             commands::cmd_list_dir,
             commands::cmd_request_thumbnail,
             commands::cmd_get_thumbnail,
+            commands::cmd_get_thumbnails,
+            commands::cmd_request_thumbnails,
             commands::cmd_open_file,
+            commands::cmd_add_root,
+            commands::cmd_remove_root,
+            commands::cmd_list_roots,
+            commands::cmd_subscribe,
             commands::cmd_shutdown,
             system::get_sidebar_entries
         ])
@@ -1,8 +1,9 @@
-import { useEffect, useState } from "react";
+import { useEffect, useRef, useState } from "react";
 import { ExplorerItem } from "../constants/ExplorerItem";
-import { listDir } from "../services/daemon";
+import { ListOptions, listDir } from "../services/daemon";
 import { adaptEntry } from "../lib/adapt";
 import { TabState } from "../contexts/TabContext";
+import { SortMode, SortOrder } from "../lib/sorting/types";
 
 interface UseEntriesType {
     entries: ExplorerItem[],
@@ -10,9 +11,27 @@ interface UseEntriesType {
     refresh: () => void
 }
 
+const sortKeys: Record<SortMode, ListOptions["sort"]> = {
+    name: "name",
+    date: "modified",
+    size: "size",
+    type: "extension"
+}
+
+// The views still sort what they show, but asking the daemon for the same
+// order means the first chunks of a big folder are the ones shown first.
+function listOptions(mode: SortMode, order: SortOrder): ListOptions {
+    return {
+        sort: sortKeys[mode],
+        direction: order === "asc" ? "ascending" : "descending",
+        name_order: "natural"
+    }
+}
+
 export default function useEntries(tab: TabState): UseEntriesType {
     const [entries, setEntries] = useState<ExplorerItem[]>([])
     const [loading, setLoading] = useState(false)
+    const latest = useRef(0)
 
     async function getEntries() {
         const location = tab.location
@@ -20,16 +39,22 @@ export default function useEntries(tab: TabState): UseEntriesType {
         
         console.log("[Explorer] requesting listDir:", location);
         
+        const request = ++latest.current
         setLoading(true)
         setEntries([])
 
         try {
-            const data = await listDir(location)
-            setEntries((data || []).map(adaptEntry))    
+            await listDir(location, (event) => {
+                // Chunks of a listing that was replaced in the meantime.
+                if (request !== latest.current || event.event !== "chunk") return
+
+                const items = event.data.entries.map(adaptEntry)
+                setEntries(prev => prev.concat(items))
+            }, listOptions(tab.sortMode, tab.sortOrder))
         } catch (e) {
             console.error(e)
         } finally {
-            setLoading(false)
+            if (request === latest.current) setLoading(false)
         }
     }
 
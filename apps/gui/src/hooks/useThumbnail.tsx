@@ -1,6 +1,6 @@
 import { useEffect, useState } from "react"
 import { getThumbnail, requestThumbnail } from "../services/daemon"
-import { bytesToDataUrl } from "../lib/bytes"
+import { bytesToObjectUrl } from "../lib/bytes"
 
 const CACHE = new Map<string, string>()
 const INFLIGHT = new Set<string>()
@@ -27,8 +27,8 @@ export default function useThumbnail(id: string, enabled = true): string | null
                 const bytes = await getThumbnail(id)
                 console.log(bytes)
 
-                if (bytes?.length) {
-                    const url = bytesToDataUrl(bytes)
+                if (bytes?.byteLength) {
+                    const url = bytesToObjectUrl(bytes)
                     CACHE.set(id, url)
                     INFLIGHT.delete(id)
     
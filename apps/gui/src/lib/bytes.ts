@@ -1,8 +1,3 @@
-export function bytesToDataUrl(bytes: number[]): string {
-    const bin = Uint8Array.from(bytes)
-    const base64 = btoa(
-        bin.reduce((s, b) => s + String.fromCharCode(b), "")
-    )
-
-    return `data:image/webp;base64,${base64}`
+export function bytesToObjectUrl(bytes: ArrayBuffer): string {
+    return URL.createObjectURL(new Blob([bytes], { type: "image/webp" }))
 }
\ No newline at end of file
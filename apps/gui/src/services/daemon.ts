@@ -1,24 +1,48 @@
-import { invoke } from "@tauri-apps/api/core"
+import { Channel, invoke } from "@tauri-apps/api/core"
+import { listen } from "@tauri-apps/api/event"
 
 export type FileEntry = {
 	id: string,
 	path: string,
 	size: number,
 	is_dir: boolean,
+	kind: "file" | "directory" | "symlink" | "other",
+	link_target?: string,
+	link_broken?: boolean,
 	modified?: number,
-	has_thumbnail: boolean
+	has_thumbnail: boolean,
+	score?: number,
+	matches?: [number, number][]
 }
 
+export type SearchMode = "substring" | "fuzzy" | "query"
+
 export async function connect() {
 	return await invoke<void>("cmd_connect")
 }
 
-export async function search(query: string, limit?: number) {
-	return await invoke<FileEntry[]>("cmd_search", { query, limit })
+export async function search(query: string, limit?: number, mode?: SearchMode) {
+	return await invoke<FileEntry[]>("cmd_search", { query, limit, mode })
+}
+
+export type ListingEvent =
+	| { event: "started", data: { total: number } }
+	| { event: "chunk", data: { entries: FileEntry[] } }
+
+/** Lists a folder a chunk at a time; resolves once every chunk has been delivered. */
+export type ListOptions = {
+	sort?: "name" | "size" | "modified" | "created" | "extension" | "kind",
+	direction?: "ascending" | "descending",
+	name_order?: "ordinal" | "case_insensitive" | "natural",
+	folders_first?: boolean,
+	include_hidden?: boolean,
+	extensions?: string[]
 }
 
-export async function listDir(path: string) {
-	return await invoke<FileEntry[]>("cmd_list_dir", { path });
+export async function listDir(path: string, onEvent: (event: ListingEvent) => void, options?: ListOptions) {
+	const channel = new Channel<ListingEvent>()
+	channel.onmessage = onEvent
+	return await invoke<void>("cmd_list_dir", { path, options, onEvent: channel });
 }
 
 export async function requestThumbnail(id: string) {
@@ -26,13 +50,91 @@ export async function requestThumbnail(id: string) {
 }
 
 export async function getThumbnail(id: string) {
-	return await invoke<number[]>("cmd_get_thumbnail", { id });
+	return await invoke<ArrayBuffer>("cmd_get_thumbnail", { id });
+}
+
+export type ThumbnailPriority = "low" | "normal" | "high"
+
+export type ThumbnailBatch = {
+	ready: Map<string, ArrayBuffer>,
+	pending: string[],
+	missing: string[]
+}
+
+function unpackThumbnails(buf: ArrayBuffer): ThumbnailBatch {
+	const headLen = new DataView(buf).getUint32(0)
+	const head: { ready: { id: string, len: number }[], pending: string[], missing: string[] } =
+		JSON.parse(new TextDecoder().decode(new Uint8Array(buf, 4, headLen)))
+
+	const ready = new Map<string, ArrayBuffer>()
+	let offset = 4 + headLen
+	for (const { id, len } of head.ready) {
+		ready.set(id, buf.slice(offset, offset + len))
+		offset += len
+	}
+
+	return { ready, pending: head.pending, missing: head.missing }
+}
+
+export async function getThumbnails(ids: string[]) {
+	return unpackThumbnails(await invoke<ArrayBuffer>("cmd_get_thumbnails", { ids }))
+}
+
+export async function requestThumbnails(ids: string[], priority?: ThumbnailPriority) {
+	return unpackThumbnails(await invoke<ArrayBuffer>("cmd_request_thumbnails", { ids, priority }))
 }
 
 export async function openFile(path: string) {
 	return await invoke<void>("cmd_open_file", { path })
 }
 
+export type WatchMode = "auto" | "native" | "poll"
+
+export type RootEntry = {
+	path: string,
+	exclude: string[],
+	max_depth?: number,
+	watch: boolean,
+	include_hidden: boolean,
+	follow_links: boolean,
+	watch_mode: WatchMode,
+	poll_interval_secs: number,
+	watching: boolean,
+	polling: boolean,
+	status: "pending" | "scanning" | "ready" | "failed",
+	error?: string,
+	entries: number,
+	last_scan?: number,
+	warnings?: string[]
+}
+
+export async function addRoot(path: string, options?: { exclude?: string[], maxDepth?: number, watch?: boolean, includeHidden?: boolean, followLinks?: boolean, watchMode?: WatchMode, pollIntervalSecs?: number }) {
+	return await invoke<RootEntry>("cmd_add_root", { path, ...options })
+}
+
+export async function removeRoot(path: string) {
+	return await invoke<void>("cmd_remove_root", { path })
+}
+
+export async function listRoots() {
+	return await invoke<RootEntry[]>("cmd_list_roots")
+}
+
+export type FsEvent =
+	| { kind: "created", entry: FileEntry }
+	| { kind: "modified", entry: FileEntry }
+	| { kind: "deleted", id: string, path: string }
+	| { kind: "renamed", id: string, from: string, to: string }
+	| { kind: "thumbnail_ready", id: string, path: string }
+
+export async function subscribe(paths: string[]) {
+	return await invoke<void>("cmd_subscribe", { paths })
+}
+
+export async function onFsEvent(handler: (event: FsEvent) => void) {
+	return await listen<FsEvent>("fs-event", e => handler(e.payload))
+}
+
 export async function shutdown() {
 	return await invoke<void>("cmd_shutdown");
 }
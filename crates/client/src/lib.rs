@@ -1,106 +1,257 @@
+use std::{collections::HashMap, sync::{Arc, Mutex, atomic::{AtomicU64, Ordering}}};
+
 use anyhow::{Result, anyhow};
-use base64::{Engine, engine::general_purpose};
-use serde::{Deserialize, Serialize};
-use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::TcpStream};
-
-const ADDR: &str = "localhost:9000";
-
-#[derive(Serialize)]
-#[serde(tag = "type")]
-pub enum Request {
-    Scan { root: String },
-    Search { query: String, limit: Option<usize> },
-    ListDir { path: String },
-    RequestThumbnail { id: String },
-    GetThumbnail { id: String },
-    OpenFile { path: String },
-    Shutdown
-}
+use sha2::{Digest, Sha256};
+#[cfg(unix)]
+use std::path::Path;
+
+#[cfg(unix)]
+use tokio::net::UnixStream;
+use tokio::{io::{AsyncRead, AsyncWrite, ReadHalf, WriteHalf}, net::TcpStream, sync::{mpsc::{Receiver, Sender, UnboundedReceiver, UnboundedSender, channel, unbounded_channel}, oneshot}};
+
+use lunio_protocol::{ClientHello, CodecError, Encoding, Envelope, Frame, MAX_FRAME, Reply, read_frame, write_frame};
+
+pub use lunio_protocol::{
+    CAPABILITIES, DEFAULT_TCP_ADDR, Event, FileEntry, Handshake, ListOptions, MIN_PROTOCOL_VERSION, NameOrder, PROTOCOL_VERSION,
+    Request, Response, ResponseData, RootEntry, RootOptions, ScanJob, SearchMode, SortDirection, SortKey, ThumbnailPriority,
+    WatchMode, paths,
+};
 
-#[derive(Deserialize)]
-#[serde(tag = "status")]
-pub enum Response {
-    #[serde(rename = "ok")]
-    Ok { data: Option<ResponseData> },
+/// Pushed events kept for [`Client::events`] before new ones are dropped.
+pub const EVENT_QUEUE: usize = 1024;
 
-    #[serde(rename = "error")]
-    Error { message: String },
+/// Chunks of a streamed listing held for a reader that has not got to them
+/// yet. Past that the connection stops reading until it does.
+pub const STREAM_QUEUE: usize = 8;
+
+/// Thumbnails that were ready, plus the ids still being generated and those
+/// that will not get one.
+#[derive(Debug, Clone, Default)]
+pub struct ThumbnailBatch {
+    pub ready: Vec<(String, Vec<u8>)>,
+    pub pending: Vec<String>,
+    pub missing: Vec<String>,
+}
+
+/// One page of a directory; `total` counts the whole directory.
+#[derive(Debug, Clone, Default)]
+pub struct DirectoryPage {
+    pub entries: Vec<FileEntry>,
+    pub total: usize,
 }
 
-#[derive(Deserialize)]
-#[serde(tag = "type")]
-pub enum ResponseData {
-    SearchResults { entries: Vec<FileEntry> },
-    DirectoryListing { entries: Vec<FileEntry> },
-    Thumbnail { id: String, bytes: String },
-    Ack,
+/// A directory listing that arrives a chunk at a time, from
+/// [`Client::list_dir_stream`]. Dropping it ignores the rest.
+pub struct DirectoryStream {
+    /// Entries in the whole listing, known before the first chunk.
+    pub total: usize,
+    id: u64,
+    parts: Receiver<Response>,
+    pending: Pending,
+    done: bool,
 }
 
+impl DirectoryStream {
+    /// The next chunk of entries, or `None` once the listing is complete.
+    pub async fn next_chunk(&mut self) -> Result<Option<Vec<FileEntry>>> {
+        if self.done {
+            return Ok(None);
+        }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct FileEntry {
-    pub id: String,
-    pub path: String,
-    pub size: u64,
-    pub is_dir: bool,
-    pub modified: Option<i64>,
-    pub has_thumbnail: bool,
+        match self.parts.recv().await {
+            Some(Response::Ok { data: Some(ResponseData::ListingChunk { entries }) }) => Ok(Some(entries)),
+            Some(Response::Ok { data: Some(ResponseData::ListingEnd) }) => {
+                self.done = true;
+                Ok(None)
+            }
+            Some(Response::Error { message }) => {
+                self.done = true;
+                Err(anyhow!(message))
+            }
+            Some(_) => Err(anyhow!("invalid response")),
+            None => Err(anyhow!("connection closed")),
+        }
+    }
 }
 
+impl Drop for DirectoryStream {
+    fn drop(&mut self) {
+        self.pending.lock().unwrap().remove(&self.id);
+    }
+}
 
-#[derive(Deserialize, Debug)]
-pub struct Handshake {
-    pub protocol: u8,
-    pub engine: String,
+/// Where the answer to a request goes: a single reply, or every part of a
+/// streamed one.
+enum Waiter {
+    Once(oneshot::Sender<Response>),
+    Stream(Sender<Response>),
 }
 
+type Pending = Arc<Mutex<HashMap<u64, Waiter>>>;
+
+/// A connection to the daemon. Clones share the connection, and any number
+/// of requests may be in flight on it at once.
+#[derive(Clone)]
 pub struct Client {
-    socket: TcpStream
+    frames: UnboundedSender<Frame>,
+    pending: Pending,
+    next_id: Arc<AtomicU64>,
+    events: Arc<Mutex<Option<Receiver<Event>>>>,
+    protocol: u8,
+    capabilities: Arc<[String]>,
+    encoding: Encoding,
 }
 
 impl Client {
+    /// Connects to the current user's daemon: its Unix socket where there is
+    /// one, TCP elsewhere.
     pub async fn connect() -> Result<Self> {
-        let mut socket = TcpStream::connect(ADDR).await?;
+        #[cfg(unix)]
+        return Self::connect_unix(&paths::socket_path()).await;
 
-        let len = socket.read_u32().await?;
-        let mut buf = vec![0u8; len as usize];
-        socket.read_exact(&mut buf).await?;
-        let hello: Handshake = serde_json::from_slice(&buf)?;
+        #[cfg(not(unix))]
+        return Self::connect_tcp(DEFAULT_TCP_ADDR).await;
+    }
 
-        println!("[lunio] connected to {} (protocol {})", hello.engine, hello.protocol);
+    #[cfg(unix)]
+    pub async fn connect_unix(path: &Path) -> Result<Self> {
+        let socket = UnixStream::connect(path).await
+            .map_err(|e| anyhow!("cannot reach daemon at {}: {e}", path.display()))?;
+        Self::start(socket).await
+    }
+
+    pub async fn connect_tcp(addr: &str) -> Result<Self> {
+        Self::start(TcpStream::connect(addr).await?).await
+    }
+
+    async fn start<S>(mut socket: S) -> Result<Self>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let hello: Handshake = read_frame(&mut socket, MAX_FRAME).await?.parse()?;
+
+        if hello.protocol < MIN_PROTOCOL_VERSION || hello.min_protocol > PROTOCOL_VERSION {
+            return Err(anyhow!(
+                "incompatible protocol: daemon speaks {}-{}, client {MIN_PROTOCOL_VERSION}-{PROTOCOL_VERSION}",
+                hello.min_protocol, hello.protocol
+            ));
+        }
+
+        let (protocol, capabilities) = greet(&mut socket, hello.challenge.as_deref()).await?;
+        let encoding = Encoding::negotiated(&capabilities);
+
+        let (reader, writer) = tokio::io::split(socket);
+        let pending = Pending::default();
+        let (events_tx, events) = channel(EVENT_QUEUE);
+        let (frames, frames_rx) = unbounded_channel();
+        tokio::spawn(read_frames(reader, encoding, pending.clone(), events_tx));
+        tokio::spawn(write_frames(writer, frames_rx));
 
-        Ok(Self { socket })
+        Ok(Self {
+            frames,
+            pending,
+            next_id: Arc::new(AtomicU64::new(1)),
+            events: Arc::new(Mutex::new(Some(events))),
+            protocol,
+            capabilities: capabilities.into(),
+            encoding,
+        })
     }
 
-    async fn send(&mut self, req: Request) -> Result<Response> {
-        let payload = serde_json::to_vec(&req)?;
-        self.socket.write_u32(payload.len() as u32).await?;
-        self.socket.write_all(&payload).await?;
+    /// The protocol version agreed on with the daemon.
+    pub fn protocol(&self) -> u8 {
+        self.protocol
+    }
+
+    /// Capabilities both sides support, such as `"binary"` and `"events"`.
+    pub fn capabilities(&self) -> &[String] {
+        &self.capabilities
+    }
+
+    pub fn supports(&self, capability: &str) -> bool {
+        self.capabilities.iter().any(|c| c == capability)
+    }
 
-        let len = self.socket.read_u32().await?;
-        let mut buf = vec![0u8; len as usize];
-        self.socket.read_exact(&mut buf).await?;
+    async fn send(&self, request: Request) -> Result<Response> {
+        let (tx, rx) = oneshot::channel();
+        self.submit(request, Waiter::Once(tx))?;
 
-        let resp: Response = serde_json::from_slice(&buf)?;
-        Ok(resp)
+        rx.await.map_err(|_| anyhow!("connection closed"))
+    }
+
+    /// Sends `request` under a fresh id and routes what answers it to `waiter`.
+    fn submit(&self, request: Request, waiter: Waiter) -> Result<u64> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let frame = Frame::encode(&Envelope { id: Some(id), request }, self.encoding)?;
+
+        self.pending.lock().unwrap().insert(id, waiter);
+
+        // Frames go through one writer task, so a caller that gives up half
+        // way cannot leave a partial frame on the socket.
+        if self.frames.send(frame).is_err() {
+            self.pending.lock().unwrap().remove(&id);
+            return Err(anyhow!("connection closed"));
+        }
+
+        Ok(id)
+    }
+
+    /// Takes the stream of events pushed for subscribed paths. Up to
+    /// [`EVENT_QUEUE`] events wait for this to be called and read; later ones
+    /// are dropped until there is room. Returns `None` after the first call.
+    pub fn events(&self) -> Option<Receiver<Event>> {
+        self.events.lock().unwrap().take()
+    }
+
+    /// Replaces the paths events are pushed for; an empty list unsubscribes.
+    pub async fn subscribe(&self, paths: Vec<String>) -> Result<()> {
+        let resp = self.send(Request::Subscribe { paths }).await?;
+
+        match resp {
+            Response::Ok { data: Some(ResponseData::Ack) } => Ok(()),
+            Response::Error { message } => Err(anyhow!(message)),
+            _ => Err(anyhow!("invalid response")),
+        }
     }
     
-    pub async fn search(&mut self, query: impl Into<String>, limit: Option<usize>) -> Result<Vec<FileEntry>> {
+    pub async fn search(&self, query: impl Into<String>, limit: Option<usize>, mode: SearchMode) -> Result<Vec<FileEntry>> {
         let resp = self.send(Request::Search {
             query: query.into(),
             limit,
+            mode,
         }).await?;
 
         match resp {
             Response::Ok { data: Some(ResponseData::SearchResults { entries }) } => Ok(entries),
-            Response::Ok { .. } => Err(anyhow!("unexpected response")),
             Response::Error { message } => Err(anyhow!(message)),
+            _ => Err(anyhow!("unexpected response")),
         }
     }
 
-    pub async fn scan(&mut self, root: impl Into<String>) -> Result<()> {
+    pub async fn scan(&self, root: impl Into<String>) -> Result<ScanJob> {
         let resp = self.send(Request::Scan { root: root.into() }).await?;
 
+        match resp {
+            Response::Ok { data: Some(ResponseData::ScanJobs { mut jobs }) } if jobs.len() == 1 => Ok(jobs.remove(0)),
+            Response::Error { message } => Err(anyhow!(message)),
+            _ => Err(anyhow!("invalid response")),
+        }
+    }
+
+    /// Progress of one scan job, or of all recent ones when `id` is `None`.
+    pub async fn scan_status(&self, id: Option<u64>) -> Result<Vec<ScanJob>> {
+        let resp = self.send(Request::ScanStatus { id }).await?;
+
+        match resp {
+            Response::Ok { data: Some(ResponseData::ScanJobs { jobs }) } => Ok(jobs),
+            Response::Error { message } => Err(anyhow!(message)),
+            _ => Err(anyhow!("invalid response")),
+        }
+    }
+
+    pub async fn cancel_scan(&self, id: u64) -> Result<()> {
+        let resp = self.send(Request::CancelScan { id }).await?;
+
         match resp {
             Response::Ok { data: Some(ResponseData::Ack) } => Ok(()),
             Response::Error { message } => Err(anyhow!(message)),
@@ -108,17 +259,50 @@ impl Client {
         }
     }
     
-    pub async fn list_dir(&mut self, path: impl Into<String>) -> Result<Vec<FileEntry>> {
-        let resp = self.send(Request::ListDir { path: path.into() }).await?;
+    pub async fn list_dir(&self, path: impl Into<String>) -> Result<Vec<FileEntry>> {
+        Ok(self.list_dir_page(path, 0, None, ListOptions::default()).await?.entries)
+    }
+
+    /// Up to `limit` entries of a directory, starting at `offset` in the
+    /// order `options` asks for.
+    pub async fn list_dir_page(
+        &self,
+        path: impl Into<String>,
+        offset: usize,
+        limit: Option<usize>,
+        options: ListOptions,
+    ) -> Result<DirectoryPage> {
+        let resp = self.send(Request::ListDir { path: path.into(), offset, limit, stream: false, options }).await?;
 
         match resp {
-            Response::Ok { data: Some(ResponseData::DirectoryListing { entries }) } => Ok(entries),
+            Response::Ok { data: Some(ResponseData::DirectoryListing { entries, total }) } => Ok(DirectoryPage { entries, total }),
             Response::Error { message } => Err(anyhow!(message)),
             _ => Err(anyhow!("invalid response")),
         }
     }
 
-    pub async fn request_thumbnail(&mut self, id: String) -> Result<()> {
+    /// Lists a directory in chunks, so the first entries can be shown before
+    /// the rest have arrived. Once [`STREAM_QUEUE`] chunks are waiting, other
+    /// replies on the connection wait too, so read the stream or drop it.
+    pub async fn list_dir_stream(&self, path: impl Into<String>, options: ListOptions) -> Result<DirectoryStream> {
+        let (tx, parts) = channel(STREAM_QUEUE);
+        let request = Request::ListDir { path: path.into(), offset: 0, limit: None, stream: true, options };
+        let id = self.submit(request, Waiter::Stream(tx))?;
+
+        let mut stream = DirectoryStream { total: 0, id, parts, pending: self.pending.clone(), done: false };
+
+        match stream.parts.recv().await {
+            Some(Response::Ok { data: Some(ResponseData::ListingStart { total }) }) => {
+                stream.total = total;
+                Ok(stream)
+            }
+            Some(Response::Error { message }) => Err(anyhow!(message)),
+            Some(_) => Err(anyhow!("invalid response")),
+            None => Err(anyhow!("connection closed")),
+        }
+    }
+
+    pub async fn request_thumbnail(&self, id: String) -> Result<()> {
         let resp = self.send(Request::RequestThumbnail { id }).await?;
 
         match resp {
@@ -128,20 +312,29 @@ impl Client {
         }
     }
 
-    pub async fn get_thumbnail(&mut self, id: String) -> Result<Vec<u8>> {
+    pub async fn get_thumbnail(&self, id: String) -> Result<Vec<u8>> {
         let resp = self.send(Request::GetThumbnail { id: id.clone() }).await?;
 
         match resp {
-            Response::Ok { data: Some(ResponseData::Thumbnail { bytes, .. }) } => {
-                let decoded = general_purpose::STANDARD.decode(bytes)?;
-                Ok(decoded)
-            }
+            Response::Binary { bytes, .. } => Ok(bytes),
             Response::Error { message } => Err(anyhow!(message)),
             _ => Err(anyhow!("invalid response")),
         }
     }
 
-    pub async fn open_file(&mut self, path: String) -> Result<()> {
+    /// Cached thumbnails for `ids`, without queuing anything.
+    pub async fn get_thumbnails(&self, ids: Vec<String>) -> Result<ThumbnailBatch> {
+        let resp = self.send(Request::GetThumbnails { ids }).await?;
+        thumbnail_batch(resp)
+    }
+
+    /// Queues whatever is not cached yet and returns what already is.
+    pub async fn request_thumbnails(&self, ids: Vec<String>, priority: ThumbnailPriority) -> Result<ThumbnailBatch> {
+        let resp = self.send(Request::RequestThumbnails { ids, priority }).await?;
+        thumbnail_batch(resp)
+    }
+
+    pub async fn open_file(&self, path: String) -> Result<()> {
         let resp = self.send(Request::OpenFile { path: path.clone() }).await?;
 
         match resp {
@@ -151,7 +344,41 @@ impl Client {
         }
     }
 
-    pub async fn shutdown(&mut self) -> Result<()> {
+    pub async fn add_root(
+        &self,
+        path: impl Into<String>,
+        options: RootOptions
+    ) -> Result<RootEntry> {
+        let resp = self.send(Request::AddRoot { path: path.into(), options }).await?;
+
+        match resp {
+            Response::Ok { data: Some(ResponseData::Roots { mut roots }) } if roots.len() == 1 => Ok(roots.remove(0)),
+            Response::Error { message } => Err(anyhow!(message)),
+            _ => Err(anyhow!("invalid response"))
+        }
+    }
+
+    pub async fn remove_root(&self, path: impl Into<String>) -> Result<()> {
+        let resp = self.send(Request::RemoveRoot { path: path.into() }).await?;
+
+        match resp {
+            Response::Ok { data: Some(ResponseData::Ack) } => Ok(()),
+            Response::Error { message } => Err(anyhow!(message)),
+            _ => Err(anyhow!("invalid response"))
+        }
+    }
+
+    pub async fn list_roots(&self) -> Result<Vec<RootEntry>> {
+        let resp = self.send(Request::ListRoots).await?;
+
+        match resp {
+            Response::Ok { data: Some(ResponseData::Roots { roots }) } => Ok(roots),
+            Response::Error { message } => Err(anyhow!(message)),
+            _ => Err(anyhow!("invalid response"))
+        }
+    }
+
+    pub async fn shutdown(&self) -> Result<()> {
         let resp = self.send(Request::Shutdown).await?;
 
         match resp {
@@ -159,4 +386,122 @@ impl Client {
             _ => Err(anyhow!("shutdown failed")),
         }
     }
-}
\ No newline at end of file
+}
+
+async fn write_frames<S: AsyncWrite>(mut writer: WriteHalf<S>, mut frames: UnboundedReceiver<Frame>) {
+    while let Some(frame) = frames.recv().await {
+        if write_frame(&mut writer, &frame).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Hands each reply to the request waiting for its id and forwards events.
+/// Once the connection drops, every waiting request fails.
+async fn read_frames<S: AsyncRead>(mut reader: ReadHalf<S>, encoding: Encoding, pending: Pending, events: Sender<Event>) {
+    while let Ok(frame) = read_frame(&mut reader, MAX_FRAME).await {
+        match decode_reply(frame, encoding) {
+            Ok(Reply { response: Response::Event { event }, .. }) => {
+                let _ = events.try_send(event);
+            }
+            Ok(Reply { id: Some(id), response }) => {
+                let parts = {
+                    let mut pending = pending.lock().unwrap();
+
+                    match pending.remove(&id) {
+                        Some(Waiter::Once(waiting)) => {
+                            let _ = waiting.send(response);
+                            continue;
+                        }
+                        // Streams stay registered until their reader drops them.
+                        Some(Waiter::Stream(parts)) => {
+                            pending.insert(id, Waiter::Stream(parts.clone()));
+                            parts
+                        }
+                        None => continue
+                    }
+                };
+
+                // Waiting here holds the socket back, and with it the daemon,
+                // rather than buffering a listing nobody is reading yet.
+                let _ = parts.send(response).await;
+            }
+            // Errors without an id answer nothing this client is waiting on.
+            Ok(Reply { id: None, .. }) | Err((None, _)) => {}
+            Err((Some(id), err)) => {
+                let response = Response::Error { message: format!("unreadable reply: {err}") };
+                let waiter = pending.lock().unwrap().remove(&id);
+
+                match waiter {
+                    Some(Waiter::Once(waiting)) => {
+                        let _ = waiting.send(response);
+                    }
+                    Some(Waiter::Stream(parts)) => {
+                        let _ = parts.send(response).await;
+                    }
+                    None => {}
+                }
+            }
+        }
+    }
+
+    pending.lock().unwrap().clear();
+}
+
+/// Decodes a reply; when that fails, the id it was meant for comes back
+/// with the error if it can still be read.
+fn decode_reply(frame: Frame, encoding: Encoding) -> Result<Reply, (Option<u64>, CodecError)> {
+    if frame.is_binary() {
+        let id = frame.request_id(encoding);
+        return frame.into_reply(encoding).map_err(|err| (id, err));
+    }
+
+    frame.decode(encoding).map_err(|err| (frame.request_id(encoding), err))
+}
+
+/// Answers the daemon's greeting with what this client speaks, proving it
+/// can read the daemon's token when challenged, and returns what was agreed.
+async fn greet<S: AsyncRead + AsyncWrite + Unpin>(socket: &mut S, challenge: Option<&str>) -> Result<(u8, Vec<String>)> {
+    let proof = match challenge {
+        Some(challenge) => {
+            let path = paths::token_path();
+            let token = std::fs::read_to_string(&path)
+                .map_err(|e| anyhow!("cannot read daemon token at {}: {e}", path.display()))?;
+
+            Some(format!("{:x}", Sha256::digest(format!("{challenge}:{}", token.trim()))))
+        }
+        None => None,
+    };
+
+    let hello = ClientHello::Hello {
+        min_protocol: MIN_PROTOCOL_VERSION,
+        max_protocol: PROTOCOL_VERSION,
+        capabilities: CAPABILITIES.iter().map(|c| c.to_string()).collect(),
+        proof,
+    };
+    write_frame(socket, &Frame::json(&hello)?).await?;
+
+    match read_frame(socket, MAX_FRAME).await?.parse()? {
+        Response::Ok { data: Some(ResponseData::Welcome { protocol, capabilities }) } => Ok((protocol, capabilities)),
+        Response::Error { message } => Err(anyhow!(message)),
+        _ => Err(anyhow!("invalid response")),
+    }
+}
+
+fn thumbnail_batch(resp: Response) -> Result<ThumbnailBatch> {
+    match resp {
+        Response::Binary { data: ResponseData::Thumbnails { ready, pending, missing }, mut bytes } => {
+            let mut out = ThumbnailBatch { ready: Vec::with_capacity(ready.len()), pending, missing };
+
+            for slice in ready.into_iter().rev() {
+                let at = bytes.len().checked_sub(slice.len).ok_or_else(|| anyhow!("thumbnail data too short"))?;
+                out.ready.push((slice.id, bytes.split_off(at)));
+            }
+            out.ready.reverse();
+
+            Ok(out)
+        }
+        Response::Error { message } => Err(anyhow!(message)),
+        _ => Err(anyhow!("invalid response")),
+    }
+}
@@ -0,0 +1,129 @@
+#![cfg(unix)]
+
+use std::{path::PathBuf, time::Duration};
+
+use lunio_client::{Client, EVENT_QUEUE, ListOptions, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION, STREAM_QUEUE};
+use lunio_protocol::{ClientHello, Envelope, Event, Frame, Handshake, MAX_FRAME, Reply, Response, ResponseData, read_frame, write_frame};
+use serde_json::json;
+use tokio::net::{UnixListener, UnixStream};
+
+/// A daemon that greets one client and hands back the connection.
+async fn fake_daemon(name: &str) -> (PathBuf, tokio::task::JoinHandle<UnixStream>) {
+    let path = std::env::temp_dir().join(format!("lunio-{name}-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path).unwrap();
+
+    let serving = tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+
+        let greeting = Handshake {
+            protocol: PROTOCOL_VERSION,
+            min_protocol: MIN_PROTOCOL_VERSION,
+            engine: "fake".into(),
+            capabilities: Vec::new(),
+            challenge: None
+        };
+        write_frame(&mut socket, &Frame::json(&greeting).unwrap()).await.unwrap();
+
+        let _: ClientHello = read_frame(&mut socket, MAX_FRAME).await.unwrap().parse().unwrap();
+        let welcome = ResponseData::Welcome { protocol: PROTOCOL_VERSION, capabilities: Vec::new() };
+        write_frame(&mut socket, &Frame::json(&Response::Ok { data: Some(welcome) }).unwrap()).await.unwrap();
+
+        socket
+    });
+
+    (path, serving)
+}
+
+#[tokio::test]
+async fn unreadable_replies_fail_their_request() {
+    let (path, serving) = fake_daemon("unreadable").await;
+    let client = Client::connect_unix(&path).await.unwrap();
+    let mut socket = serving.await.unwrap();
+
+    let asking = tokio::spawn({
+        let client = client.clone();
+        async move { client.list_roots().await }
+    });
+
+    let request: Envelope = read_frame(&mut socket, MAX_FRAME).await.unwrap().parse().unwrap();
+    let garbled = json!({ "request_id": request.id, "status": "ok", "data": { "type": "NoSuchData" } });
+    write_frame(&mut socket, &Frame::json(&garbled).unwrap()).await.unwrap();
+
+    let answered = tokio::time::timeout(Duration::from_secs(5), asking).await.expect("request was left waiting");
+    let err = answered.unwrap().unwrap_err();
+    assert!(err.to_string().contains("unreadable reply"), "{err}");
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn unread_events_are_capped() {
+    let (path, serving) = fake_daemon("events").await;
+    let client = Client::connect_unix(&path).await.unwrap();
+    let mut socket = serving.await.unwrap();
+
+    let asking = tokio::spawn({
+        let client = client.clone();
+        async move { client.list_roots().await }
+    });
+    let request: Envelope = read_frame(&mut socket, MAX_FRAME).await.unwrap().parse().unwrap();
+
+    for i in 0..EVENT_QUEUE + 10 {
+        let event = Event::Deleted { id: i.to_string(), path: format!("/w/{i}") };
+        let reply = Reply { id: None, response: Response::Event { event } };
+        write_frame(&mut socket, &Frame::json(&reply).unwrap()).await.unwrap();
+    }
+
+    // Everything pushed before the answer has been read once it arrives.
+    let answer = Reply { id: request.id, response: Response::Ok { data: Some(ResponseData::Roots { roots: Vec::new() }) } };
+    write_frame(&mut socket, &Frame::json(&answer).unwrap()).await.unwrap();
+    asking.await.unwrap().unwrap();
+
+    let mut events = client.events().unwrap();
+    let mut kept = 0;
+    while events.try_recv().is_ok() {
+        kept += 1;
+    }
+    assert_eq!(kept, EVENT_QUEUE);
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn unread_listings_hold_the_connection_back() {
+    let (path, serving) = fake_daemon("stream").await;
+    let client = Client::connect_unix(&path).await.unwrap();
+    let mut socket = serving.await.unwrap();
+
+    let listing = tokio::spawn({
+        let client = client.clone();
+        async move { client.list_dir_stream("/big", ListOptions::default()).await }
+    });
+    let request: Envelope = read_frame(&mut socket, MAX_FRAME).await.unwrap().parse().unwrap();
+    let part = |data| Frame::json(&Reply { id: request.id, response: Response::Ok { data: Some(data) } }).unwrap();
+
+    write_frame(&mut socket, &part(ResponseData::ListingStart { total: 0 })).await.unwrap();
+    let mut stream = listing.await.unwrap().unwrap();
+    for _ in 0..=STREAM_QUEUE {
+        write_frame(&mut socket, &part(ResponseData::ListingChunk { entries: Vec::new() })).await.unwrap();
+    }
+
+    let asking = tokio::spawn({
+        let client = client.clone();
+        async move { client.list_roots().await }
+    });
+    let roots: Envelope = read_frame(&mut socket, MAX_FRAME).await.unwrap().parse().unwrap();
+    let answer = Reply { id: roots.id, response: Response::Ok { data: Some(ResponseData::Roots { roots: Vec::new() }) } };
+    write_frame(&mut socket, &Frame::json(&answer).unwrap()).await.unwrap();
+
+    // The answer sits behind a chunk there is no room for yet.
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(!asking.is_finished());
+
+    stream.next_chunk().await.unwrap();
+    let answered = tokio::time::timeout(Duration::from_secs(5), asking).await.expect("request was left waiting");
+    assert!(answered.unwrap().is_ok());
+
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,142 @@
+use std::{fs, path::{Path, PathBuf}, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::fs::{filter::PathFilter, mounts::mount_of};
+
+/// How changes below a root are picked up.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WatchMode {
+    /// Native events, or polling on network and FUSE mounts.
+    #[default]
+    Auto,
+    Native,
+    Poll
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RootConfig {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    #[serde(default)]
+    pub max_depth: Option<usize>,
+    #[serde(default = "default_true")]
+    pub watch: bool,
+    #[serde(default = "default_true")]
+    pub ignore_files: bool,
+    #[serde(default = "default_true")]
+    pub include_hidden: bool,
+    #[serde(default)]
+    pub follow_links: bool,
+    #[serde(default)]
+    pub watch_mode: WatchMode,
+    #[serde(default = "default_poll_interval")]
+    pub poll_interval_secs: u64
+}
+
+impl RootConfig {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            exclude: Vec::new(),
+            max_depth: None,
+            watch: true,
+            ignore_files: true,
+            include_hidden: true,
+            follow_links: false,
+            watch_mode: WatchMode::Auto,
+            poll_interval_secs: default_poll_interval()
+        }
+    }
+
+    /// The polling interval to watch this root with, or `None` for native
+    /// events.
+    pub fn poll_interval(&self) -> Option<Duration> {
+        let poll = match self.watch_mode {
+            WatchMode::Native => false,
+            WatchMode::Poll => true,
+            WatchMode::Auto => mount_of(&self.path).is_some_and(|m| m.needs_polling())
+        };
+
+        poll.then(|| Duration::from_secs(self.poll_interval_secs.max(1)))
+    }
+
+    /// Builds the filter for this root from its own excludes plus the global
+    /// ones.
+    pub fn filter(&self, global_exclude: &[String]) -> anyhow::Result<PathFilter> {
+        let patterns: Vec<String> = global_exclude
+            .iter()
+            .chain(&self.exclude)
+            .cloned()
+            .collect();
+
+        PathFilter::new(&self.path, &patterns, self.ignore_files, self.include_hidden)
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_poll_interval() -> u64 {
+    10
+}
+
+fn default_exclude() -> Vec<String> {
+    vec![".git".into()]
+}
+
+fn default_debounce_ms() -> u64 {
+    100
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EngineConfig {
+    #[serde(default)]
+    pub roots: Vec<RootConfig>,
+    #[serde(default = "default_exclude")]
+    pub exclude: Vec<String>,
+    /// How long watcher changes are collected before being applied together.
+    #[serde(default = "default_debounce_ms")]
+    pub debounce_ms: u64
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        Self {
+            roots: Vec::new(),
+            exclude: default_exclude(),
+            debounce_ms: default_debounce_ms()
+        }
+    }
+}
+
+impl EngineConfig {
+    pub fn load(path: &Path) -> Self {
+        let Ok(bytes) = fs::read(path) else {
+            return Self::default();
+        };
+
+        match serde_json::from_slice(&bytes) {
+            Ok(cfg) => cfg,
+            Err(e) => {
+                eprintln!("[engine] ignoring malformed config {:?}: {e}", path);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(self)?)?;
+        fs::rename(tmp, path)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,62 @@
+use std::{path::PathBuf, sync::Arc};
+
+use parking_lot::RwLock;
+
+use crate::models::{FileId, FileMeta};
+
+/// Something clients showing the index may want to react to.
+#[derive(Debug, Clone)]
+pub enum EngineEvent {
+    Created(FileMeta),
+    Modified(FileMeta),
+    Deleted { id: FileId, path: PathBuf },
+    Renamed { id: FileId, from: PathBuf, to: PathBuf },
+    ThumbnailReady { id: FileId, path: PathBuf }
+}
+
+impl EngineEvent {
+    /// Every path the event touches, for matching it against subscriptions.
+    pub fn paths(&self) -> Vec<&PathBuf> {
+        match self {
+            EngineEvent::Created(meta) | EngineEvent::Modified(meta) => vec![&meta.path],
+            EngineEvent::Deleted { path, .. } | EngineEvent::ThumbnailReady { path, .. } => vec![path],
+            EngineEvent::Renamed { from, to, .. } => vec![from, to]
+        }
+    }
+}
+
+/// Returns false once it no longer wants events, which unsubscribes it.
+pub type Subscriber = Box<dyn Fn(&EngineEvent) -> bool + Send + Sync>;
+
+#[derive(Default)]
+pub struct EventBus {
+    subscribers: RwLock<Vec<Arc<Subscriber>>>
+}
+
+impl EventBus {
+    pub fn subscribe(&self, subscriber: Subscriber) {
+        self.subscribers.write().push(Arc::new(subscriber));
+    }
+
+    pub fn publish(&self, event: &EngineEvent) {
+        self.publish_all(std::slice::from_ref(event));
+    }
+
+    /// Delivers to a snapshot of the subscribers, so one that is slow, or
+    /// that subscribes from its callback, never holds up the lock.
+    pub fn publish_all(&self, events: &[EngineEvent]) {
+        if events.is_empty() {
+            return;
+        }
+
+        let subscribers = self.subscribers.read().clone();
+        let gone: Vec<Arc<Subscriber>> = subscribers
+            .into_iter()
+            .filter(|deliver| !events.iter().all(|e| deliver(e)))
+            .collect();
+
+        if !gone.is_empty() {
+            self.subscribers.write().retain(|s| !gone.iter().any(|g| Arc::ptr_eq(s, g)));
+        }
+    }
+}
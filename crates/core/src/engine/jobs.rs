@@ -0,0 +1,159 @@
+use std::{collections::HashMap, ops::Deref, path::PathBuf, sync::{Arc, atomic::{AtomicU64, Ordering}}, time::{Duration, Instant}};
+
+use parking_lot::RwLock;
+
+use crate::fs::scan::ScanProgress;
+
+pub type JobId = u64;
+
+/// Finished jobs stay queryable until this many newer ones have finished.
+const KEEP_FINISHED: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobState {
+    Running,
+    Completed,
+    Cancelled,
+    /// The scan stopped without finishing, as when its thread panicked.
+    Failed
+}
+
+#[derive(Debug, Clone)]
+pub struct ScanJobInfo {
+    pub id: JobId,
+    pub root: PathBuf,
+    pub state: JobState,
+    pub dirs: usize,
+    pub files: usize,
+    pub errors: usize,
+    pub elapsed: Duration
+}
+
+pub(crate) struct ScanJob {
+    pub id: JobId,
+    pub root: PathBuf,
+    pub progress: Arc<ScanProgress>,
+    started: Instant,
+    finished: RwLock<Option<(JobState, Duration)>>
+}
+
+impl ScanJob {
+    pub fn finish(&self, state: JobState) {
+        *self.finished.write() = Some((state, self.started.elapsed()));
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.finished.read().is_none()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.progress.is_cancelled()
+    }
+
+    pub fn info(&self) -> ScanJobInfo {
+        let (state, elapsed) = self.finished
+            .read()
+            .unwrap_or_else(|| (JobState::Running, self.started.elapsed()));
+
+        ScanJobInfo {
+            id: self.id,
+            root: self.root.clone(),
+            state,
+            dirs: self.progress.dirs.load(Ordering::Relaxed),
+            files: self.progress.files.load(Ordering::Relaxed),
+            errors: self.progress.errors.load(Ordering::Relaxed),
+            elapsed
+        }
+    }
+}
+
+/// The scanning side's hold on a job. Dropping it before the job is
+/// finished, say by panicking, marks the job failed instead of leaving it
+/// running for good.
+pub(crate) struct RunningJob(Arc<ScanJob>);
+
+impl RunningJob {
+    pub fn job(&self) -> Arc<ScanJob> {
+        self.0.clone()
+    }
+}
+
+impl Deref for RunningJob {
+    type Target = ScanJob;
+
+    fn deref(&self) -> &ScanJob {
+        &self.0
+    }
+}
+
+impl Drop for RunningJob {
+    fn drop(&mut self) {
+        if self.is_running() {
+            self.finish(JobState::Failed);
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct ScanJobs {
+    next: AtomicU64,
+    jobs: RwLock<HashMap<JobId, Arc<ScanJob>>>
+}
+
+impl ScanJobs {
+    pub fn start(&self, root: PathBuf) -> RunningJob {
+        let job = Arc::new(ScanJob {
+            id: self.next.fetch_add(1, Ordering::Relaxed) + 1,
+            root,
+            progress: Arc::default(),
+            started: Instant::now(),
+            finished: RwLock::new(None)
+        });
+
+        let mut jobs = self.jobs.write();
+        jobs.insert(job.id, job.clone());
+        prune(&mut jobs);
+
+        RunningJob(job)
+    }
+
+    pub fn get(&self, id: JobId) -> Option<ScanJobInfo> {
+        self.jobs.read().get(&id).map(|j| j.info())
+    }
+
+    pub fn list(&self) -> Vec<ScanJobInfo> {
+        let mut out: Vec<ScanJobInfo> = self.jobs.read().values().map(|j| j.info()).collect();
+        out.sort_by_key(|j| j.id);
+        out
+    }
+
+    /// Asks a running job to stop; returns false if it is unknown or done.
+    pub fn cancel(&self, id: JobId) -> bool {
+        match self.jobs.read().get(&id) {
+            Some(job) if job.is_running() => {
+                job.progress.cancel();
+                true
+            }
+            _ => false
+        }
+    }
+
+    pub fn cancel_all(&self) {
+        for job in self.jobs.read().values() {
+            job.progress.cancel();
+        }
+    }
+}
+
+fn prune(jobs: &mut HashMap<JobId, Arc<ScanJob>>) {
+    let mut finished: Vec<JobId> = jobs.values().filter(|j| !j.is_running()).map(|j| j.id).collect();
+
+    if finished.len() <= KEEP_FINISHED {
+        return;
+    }
+
+    finished.sort_unstable();
+    for id in &finished[..finished.len() - KEEP_FINISHED] {
+        jobs.remove(id);
+    }
+}
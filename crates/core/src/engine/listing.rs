@@ -0,0 +1,199 @@
+use std::cmp::Ordering;
+
+use crate::{fs::metadata::is_hidden, models::{FileKind, FileMeta}};
+
+/// What a directory listing is ordered by. Ties are broken by name, then by
+/// path, so the order is the same every time and pages line up.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SortKey {
+    #[default]
+    Name,
+    Size,
+    Modified,
+    Created,
+    Extension,
+    /// Files, then symlinks, then anything else; by extension within each.
+    Kind
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SortDirection {
+    #[default]
+    Ascending,
+    Descending
+}
+
+/// How names compare.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NameOrder {
+    /// Byte-wise, so `B` comes before `a` and `10` before `9`.
+    #[default]
+    Ordinal,
+    CaseInsensitive,
+    /// Case-insensitive, with runs of digits compared by value.
+    Natural
+}
+
+impl NameOrder {
+    pub fn compare(self, a: &str, b: &str) -> Ordering {
+        match self {
+            NameOrder::Ordinal => a.cmp(b),
+            NameOrder::CaseInsensitive => a.chars().flat_map(char::to_lowercase).cmp(b.chars().flat_map(char::to_lowercase)),
+            NameOrder::Natural => natural_cmp(a, b)
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ListOptions {
+    pub sort: SortKey,
+    pub direction: SortDirection,
+    pub name_order: NameOrder,
+    /// Directories go ahead of everything else, whichever the direction.
+    pub folders_first: bool,
+    pub include_hidden: bool,
+    /// Only files with one of these extensions, ignoring case and any
+    /// leading dot. Directories are kept so the tree can still be walked.
+    pub extensions: Vec<String>
+}
+
+impl Default for ListOptions {
+    fn default() -> Self {
+        Self {
+            sort: SortKey::Name,
+            direction: SortDirection::Ascending,
+            name_order: NameOrder::Ordinal,
+            folders_first: true,
+            include_hidden: true,
+            extensions: Vec::new()
+        }
+    }
+}
+
+impl ListOptions {
+    /// Filters and sorts `entries`, working out each one's sort keys once
+    /// rather than on every comparison.
+    pub fn apply(&self, entries: Vec<FileMeta>) -> Vec<FileMeta> {
+        let mut keyed: Vec<(SortKeys, FileMeta)> = entries
+            .into_iter()
+            .filter(|m| self.keeps(m))
+            .map(|m| (SortKeys::of(&m), m))
+            .collect();
+
+        keyed.sort_by(|(ka, a), (kb, b)| self.compare_keyed(ka, a, kb, b));
+        keyed.into_iter().map(|(_, m)| m).collect()
+    }
+
+    pub fn keeps(&self, meta: &FileMeta) -> bool {
+        if !self.include_hidden && is_hidden(&meta.path) {
+            return false;
+        }
+
+        if self.extensions.is_empty() || is_dir(meta) {
+            return true;
+        }
+
+        let ext = extension(meta);
+        self.extensions.iter().any(|e| e.trim_start_matches('.').eq_ignore_ascii_case(&ext))
+    }
+
+    pub fn compare(&self, a: &FileMeta, b: &FileMeta) -> Ordering {
+        self.compare_keyed(&SortKeys::of(a), a, &SortKeys::of(b), b)
+    }
+
+    fn compare_keyed(&self, ka: &SortKeys, a: &FileMeta, kb: &SortKeys, b: &FileMeta) -> Ordering {
+        if self.folders_first {
+            let group = is_dir(b).cmp(&is_dir(a));
+            if group != Ordering::Equal {
+                return group;
+            }
+        }
+
+        let by_key = match self.sort {
+            SortKey::Name => Ordering::Equal,
+            SortKey::Size => a.size.cmp(&b.size),
+            SortKey::Modified => a.modified.cmp(&b.modified),
+            SortKey::Created => a.created.cmp(&b.created),
+            SortKey::Extension => ka.extension.cmp(&kb.extension),
+            SortKey::Kind => kind_rank(a).cmp(&kind_rank(b)).then_with(|| ka.extension.cmp(&kb.extension))
+        };
+
+        let order = by_key
+            .then_with(|| self.name_order.compare(&ka.name, &kb.name))
+            .then_with(|| a.path.cmp(&b.path));
+
+        match self.direction {
+            SortDirection::Ascending => order,
+            SortDirection::Descending => order.reverse()
+        }
+    }
+}
+
+/// The parts of an entry that cost an allocation to work out.
+struct SortKeys {
+    name: String,
+    extension: String
+}
+
+impl SortKeys {
+    fn of(meta: &FileMeta) -> Self {
+        Self {
+            name: meta.path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default(),
+            extension: extension(meta)
+        }
+    }
+}
+
+fn is_dir(meta: &FileMeta) -> bool {
+    matches!(meta.kind, FileKind::Directory)
+}
+
+fn kind_rank(meta: &FileMeta) -> u8 {
+    match meta.kind {
+        FileKind::Directory => 0,
+        FileKind::File => 1,
+        FileKind::Symlink { .. } => 2,
+        FileKind::Other => 3
+    }
+}
+
+fn extension(meta: &FileMeta) -> String {
+    meta.path.extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+/// `file2` before `file10`. Numbers equal in value, like `01` and `1`,
+/// compare equal and are left to the caller's tie-break.
+fn natural_cmp(mut a: &str, mut b: &str) -> Ordering {
+    loop {
+        let (x, y) = match (a.chars().next(), b.chars().next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) => (x, y)
+        };
+
+        if x.is_ascii_digit() && y.is_ascii_digit() {
+            let (da, ra) = split_digits(a);
+            let (db, rb) = split_digits(b);
+            let (da, db) = (da.trim_start_matches('0'), db.trim_start_matches('0'));
+
+            let order = da.len().cmp(&db.len()).then_with(|| da.cmp(db));
+            if order != Ordering::Equal {
+                return order;
+            }
+            (a, b) = (ra, rb);
+        } else {
+            let order = x.to_lowercase().cmp(y.to_lowercase());
+            if order != Ordering::Equal {
+                return order;
+            }
+            (a, b) = (&a[x.len_utf8()..], &b[y.len_utf8()..]);
+        }
+    }
+}
+
+fn split_digits(s: &str) -> (&str, &str) {
+    s.split_at(s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len()))
+}
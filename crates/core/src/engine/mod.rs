@@ -1,3 +1,7 @@
 pub mod queue;
 pub mod config;
+pub mod events;
+pub mod jobs;
+pub mod listing;
+pub mod roots;
 pub mod runtime;
\ No newline at end of file
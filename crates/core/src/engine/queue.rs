@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+
+use crate::{fs::watcher::FsChange, models::FileId};
+
+/// Collects watcher changes over a debounce window and folds repeated changes
+/// to the same file into one.
+///
+/// A merged change moves to the position of the newest change it absorbed,
+/// so it is never applied ahead of something that happened before it.
+#[derive(Debug, Default)]
+pub struct ChangeQueue {
+    slots: Vec<Option<FsChange>>,
+    latest: HashMap<FileId, usize>
+}
+
+enum Merge {
+    Replace(FsChange),
+    Cancel,
+    Keep(FsChange, FsChange)
+}
+
+impl ChangeQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, mut change: FsChange) {
+        let id = change.id();
+
+        if let Some(slot) = self.latest.get(&id).copied() {
+            let prev = self.slots[slot].take().expect("latest slot is occupied");
+
+            match merge(prev, change) {
+                Merge::Replace(merged) => change = merged,
+                Merge::Cancel => {
+                    self.latest.remove(&id);
+                    return;
+                }
+                Merge::Keep(prev, next) => {
+                    self.slots[slot] = Some(prev);
+                    change = next;
+                }
+            }
+        }
+
+        self.latest.insert(id, self.slots.len());
+        self.slots.push(Some(change));
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.iter().flatten().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.latest.is_empty()
+    }
+
+    pub fn drain(&mut self) -> Vec<FsChange> {
+        self.latest.clear();
+        self.slots.drain(..).flatten().collect()
+    }
+}
+
+fn merge(prev: FsChange, next: FsChange) -> Merge {
+    use FsChange::*;
+
+    match (prev, next) {
+        (Created(..), Deleted(_)) => Merge::Cancel,
+        (Created(..), Created(id, meta) | Modified(id, meta)) => Merge::Replace(Created(id, meta)),
+        (Modified(..) | Deleted(_), Created(id, meta) | Modified(id, meta)) => Merge::Replace(Modified(id, meta)),
+        (Modified(..) | Deleted(_) | Renamed { .. }, Deleted(id)) => Merge::Replace(Deleted(id)),
+        (Renamed { id, from, .. }, Renamed { to, .. }) => Merge::Replace(Renamed { id, from, to }),
+        // A rename also moves whatever lies below it, so it cannot be folded
+        // into a plain metadata update.
+        (prev, next) => Merge::Keep(prev, next)
+    }
+}
@@ -0,0 +1,60 @@
+use std::{sync::Arc, time::SystemTime};
+
+use notify::Watcher;
+
+use crate::engine::{config::RootConfig, jobs::{JobState, ScanJob}};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RootStatus {
+    Pending,
+    Scanning,
+    Ready,
+    Failed(String)
+}
+
+#[derive(Debug, Clone)]
+pub struct RootInfo {
+    pub config: RootConfig,
+    pub status: RootStatus,
+    pub entries: usize,
+    pub last_scan: Option<SystemTime>,
+    pub watching: bool,
+    pub polling: bool,
+    pub warnings: Vec<String>
+}
+
+pub(crate) struct RootState {
+    pub info: RootInfo,
+    pub watcher: Option<Box<dyn Watcher + Send + Sync>>,
+    /// The latest scan of the root, running or not.
+    pub scan: Option<Arc<ScanJob>>
+}
+
+impl RootState {
+    pub fn new(config: RootConfig) -> Self {
+        Self {
+            info: RootInfo {
+                config,
+                status: RootStatus::Pending,
+                entries: 0,
+                last_scan: None,
+                watching: false,
+                polling: false,
+                warnings: Vec::new()
+            },
+            watcher: None,
+            scan: None
+        }
+    }
+
+    /// The root's info, counting a scan that died halfway as a failure.
+    pub fn info(&self) -> RootInfo {
+        let mut info = self.info.clone();
+
+        if info.status == RootStatus::Scanning && self.scan.as_ref().is_some_and(|j| j.info().state == JobState::Failed) {
+            info.status = RootStatus::Failed("scan stopped unexpectedly".into());
+        }
+
+        info
+    }
+}
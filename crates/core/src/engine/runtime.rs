@@ -1,19 +1,28 @@
-#[cfg(target_os = "windows")]
+#[cfg(any(target_os = "windows", target_os = "macos", target_os = "linux"))]
 use std::process::Command;
-use std::{path::{Path, PathBuf}, sync::{Arc, atomic::{AtomicBool, Ordering}}, thread::{self, JoinHandle}};
+use std::{collections::{HashMap, HashSet}, path::{Path, PathBuf}, sync::{Arc, atomic::{AtomicBool, Ordering}, mpsc::{Receiver, RecvTimeoutError, Sender, channel}}, thread::{self, JoinHandle}, time::{Duration, Instant, SystemTime}};
 
-use notify::RecommendedWatcher;
+use anyhow::anyhow;
 use parking_lot::RwLock;
 
-use crate::{fs::{scan::scan_root, watcher::{FsChange, FsWatcher, start_watcher}}, index::index::SimpleIndex, models::{FileId, FileMeta}, thumbnails::{cache::ThumbnailCache, generator::ThumbnailConfig, worker::ThumbnailWorker}};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::{engine::{config::{EngineConfig, RootConfig}, events::{EngineEvent, EventBus, Subscriber}, jobs::{JobId, JobState, RunningJob, ScanJobInfo, ScanJobs}, listing::ListOptions, queue::ChangeQueue, roots::{RootInfo, RootState, RootStatus}}, fs::{metadata::read_metadata, scan::{ScanOptions, scan_root_with}, watcher::{FsChange, IssueHandler, PathLookup, WatchIssue, start_watcher}}, index::{index::SimpleIndex, query::{Query, QueryError}, store::IndexStore}, models::{FileId, FileKind, FileMeta, SearchHit, SearchMode, ThumbnailBatch}, thumbnails::{cache::ThumbnailCache, generator::ThumbnailConfig, worker::{ThumbnailPriority, ThumbnailWorker}}};
 
 pub struct EngineRuntime {
     index: Arc<RwLock<SimpleIndex>>,
+    store: Arc<IndexStore>,
     thumb_cache: Arc<ThumbnailCache>,
     thumb_worker: ThumbnailWorker,
+    events: Arc<EventBus>,
+    config_path: PathBuf,
+    global_exclude: Arc<Vec<String>>,
+    debounce: Duration,
+    roots: Arc<RwLock<HashMap<PathBuf, RootState>>>,
+    jobs: Arc<ScanJobs>,
+    changes: Sender<FsChange>,
     stop_flag: Arc<AtomicBool>,
-    watch_thread: Arc<RwLock<Option<JoinHandle<()>>>>,
-    watcher: Arc<RwLock<Option<RecommendedWatcher>>>
+    watch_thread: Arc<RwLock<Option<JoinHandle<()>>>>
 }
 
 impl EngineRuntime {
@@ -22,82 +31,461 @@ impl EngineRuntime {
         ffmpeg: Option<PathBuf>,
         pdfium: Option<PathBuf>
     ) -> Self {
+        let store = Arc::new(IndexStore::new(cache_root.join("index.bin")));
         let cfg = ThumbnailConfig::new(cache_root.clone(), ffmpeg, pdfium);
         let cache = Arc::new(ThumbnailCache::new(cfg));
         
         let index = Arc::new(RwLock::new(SimpleIndex::new()));
 
-        let worker = ThumbnailWorker::new(cache.clone(), index.clone());
+        let events = Arc::new(EventBus::default());
+        let worker = ThumbnailWorker::new(cache.clone(), index.clone(), events.clone());
+
+        let config_path = cache_root.join("config.json");
+        let mut config = EngineConfig::load(&config_path);
+        let roots = config.roots
+            .drain(..)
+            .map(|r| (r.path.clone(), RootState::new(r)))
+            .collect();
+
+        let (tx, rx) = channel::<FsChange>();
+        let stop_flag = Arc::new(AtomicBool::new(false));
+        let debounce = Duration::from_millis(config.debounce_ms);
+        let handle = spawn_watch_loop(rx, index.clone(), cache.clone(), worker.clone(), events.clone(), debounce, stop_flag.clone());
 
         Self {
             index,
+            store,
             thumb_cache: cache,
             thumb_worker: worker,
-            stop_flag: Arc::new(AtomicBool::new(false)),
-            watch_thread: Arc::new(RwLock::new(None)),
-            watcher: Arc::new(RwLock::new(None))
+            events,
+            config_path,
+            global_exclude: Arc::new(config.exclude),
+            debounce,
+            roots: Arc::new(RwLock::new(roots)),
+            jobs: Arc::default(),
+            changes: tx,
+            stop_flag,
+            watch_thread: Arc::new(RwLock::new(Some(handle)))
         }
     }
 
-    pub fn full_scan(&self, root: impl AsRef<Path>) {
-        let metas = scan_root(root);
-        
-        self.index.write().apply_full_scan(metas);
+    /// Rescans `root` and reconciles only that part of the index with it.
+    /// The scan is listed as a job while it runs; cancelling it leaves the
+    /// index as it was.
+    pub fn full_scan(&self, root: impl AsRef<Path>) -> Vec<FsChange> {
+        let root = absolute(root.as_ref());
+        let job = self.jobs.start(root.clone());
+        let mut opts = self.scan_options_for(&root);
+        opts.progress = Some(job.progress.clone());
+        let metas = scan_root_with(&root, &opts);
+
+        if job.is_cancelled() {
+            job.finish(JobState::Cancelled);
+            return Vec::new();
+        }
+
+        let changes = apply_scan(&self.index, &self.thumb_cache, &self.events, &root, metas);
+        self.persist_index();
+        job.finish(JobState::Completed);
+
+        changes
     }
 
-    pub fn start_watcher_loop(&mut self, root: impl AsRef<Path>) {
-        let root = root.as_ref().to_path_buf();
-        let FsWatcher { rx, watcher } = start_watcher(root).expect("failed to start watcher");
-        *self.watcher.write() = Some(watcher);
+    /// Runs a scan of `root` as a background job. A cancelled job leaves the
+    /// index as it was.
+    pub fn start_scan(&self, root: impl AsRef<Path>) -> anyhow::Result<ScanJobInfo> {
+        let root = absolute(root.as_ref());
+
+        if !root.is_dir() {
+            return Err(anyhow!("{:?} is not a directory", root));
+        }
+
+        let job = self.jobs.start(root.clone());
+        let mut opts = self.scan_options_for(&root);
+        opts.progress = Some(job.progress.clone());
 
         let index = self.index.clone();
-        let worker = self.thumb_worker.clone();
-        let stop = self.stop_flag.clone();
-
-        let handle = thread::spawn(move || {
-            while !stop.load(Ordering::Relaxed) {
-                match rx.recv() {
-                    Ok(change) => {
-                        match change {
-                            FsChange::Created(id, meta) |
-                            FsChange::Modified(id, meta) => {
-                                index.write().apply_change(id, Some(meta.clone()));
-                                worker.submit(meta);
-                            }
-                            FsChange::Deleted(id) => {
-                                index.write().apply_change(id, None);
-                            }
-                        }
-                    },
-                    Err(_) => break
-                }
+        let cache = self.thumb_cache.clone();
+        let store = self.store.clone();
+        let events = self.events.clone();
+        let info = job.info();
+
+        thread::spawn(move || {
+            let metas = scan_root_with(&root, &opts);
+
+            if job.is_cancelled() {
+                return job.finish(JobState::Cancelled);
+            }
+
+            apply_scan(&index, &cache, &events, &root, metas);
+
+            if let Err(e) = store.save(&index.read()) {
+                eprintln!("[engine] failed to persist index to {:?}: {e}", store.path());
             }
+
+            job.finish(JobState::Completed);
         });
 
-        *self.watch_thread.write() = Some(handle)
+        Ok(info)
+    }
+
+    pub fn scan_status(&self, id: JobId) -> Option<ScanJobInfo> {
+        self.jobs.get(id)
+    }
+
+    pub fn list_scans(&self) -> Vec<ScanJobInfo> {
+        self.jobs.list()
+    }
+
+    pub fn cancel_scan(&self, id: JobId) -> bool {
+        self.jobs.cancel(id)
+    }
+
+    pub fn load_index(&self) -> bool {
+        match self.store.load() {
+            Ok(Some(loaded)) => {
+                *self.index.write() = loaded;
+                true
+            }
+            Ok(None) => false,
+            Err(e) => {
+                eprintln!("[engine] failed to load index from {:?}: {e}", self.store.path());
+                false
+            }
+        }
+    }
+
+    pub fn persist_index(&self) {
+        if let Err(e) = self.store.save(&self.index.read()) {
+            eprintln!("[engine] failed to persist index to {:?}: {e}", self.store.path());
+        }
+    }
+
+    /// Brings a warm-started index up to date with the disk. Directories
+    /// whose mtime moved while the daemon was down had entries added or
+    /// removed, so they are rescanned.
+    pub fn reconcile_in_background(&self) -> JoinHandle<()> {
+        let index = self.index.clone();
+        let cache = self.thumb_cache.clone();
+        let store = self.store.clone();
+        let events = self.events.clone();
+        let roots = self.roots.clone();
+        let global_exclude = self.global_exclude.clone();
+
+        thread::spawn(move || {
+            for dir in reconcile(&index, &cache) {
+                let metas = scan_root_with(&dir, &scan_options_for(&roots, &global_exclude, &dir));
+                apply_scan(&index, &cache, &events, &dir, metas);
+            }
+
+            if let Err(e) = store.save(&index.read()) {
+                eprintln!("[engine] failed to persist index to {:?}: {e}", store.path());
+            }
+        })
+    }
+
+    pub fn indexed_count(&self) -> usize {
+        self.index.read().len()
+    }
+
+    /// Starts every configured root: watchers are attached and roots missing
+    /// from the (possibly warm-started) index are scanned in the background.
+    pub fn start_roots(&self) {
+        let paths: Vec<PathBuf> = self.roots.read().keys().cloned().collect();
+
+        for path in paths {
+            let indexed = self.is_indexed(&path);
+            self.start_root(&path, !indexed);
+        }
+    }
+
+    pub fn add_root(&self, mut config: RootConfig) -> anyhow::Result<RootInfo> {
+        config.path = std::path::absolute(&config.path)?;
+
+        if !config.path.is_dir() {
+            return Err(anyhow!("{:?} is not a directory", config.path));
+        }
+
+        config.filter(&self.global_exclude)?;
+
+        let path = config.path.clone();
+        {
+            let mut roots = self.roots.write();
+            if roots.contains_key(&path) {
+                return Err(anyhow!("{:?} is already an indexed root", path));
+            }
+            roots.insert(path.clone(), RootState::new(config));
+        }
+
+        self.save_config()?;
+        self.start_root(&path, true);
+
+        self.root_info(&path).ok_or_else(|| anyhow!("root vanished while starting"))
+    }
+
+    /// Stops indexing `path`. Entries that another root still covers, above
+    /// or below it, stay in the index.
+    pub fn remove_root(&self, path: &Path) -> anyhow::Result<()> {
+        let path = std::path::absolute(path)?;
+
+        let (covered, nested) = {
+            let mut roots = self.roots.write();
+            let Some(removed) = roots.remove(&path) else {
+                return Err(anyhow!("{:?} is not an indexed root", path));
+            };
+            if let Some(scan) = &removed.scan {
+                scan.progress.cancel();
+            }
+
+            let covered = roots.keys().any(|r| path.starts_with(r));
+            let nested: Vec<PathBuf> = roots.keys().filter(|r| r.starts_with(&path)).cloned().collect();
+            (covered, nested)
+        };
+
+        if !covered {
+            let removed: Vec<EngineEvent> = {
+                let mut idx = self.index.write();
+                let kept: HashSet<FileId> = nested.iter().flat_map(|r| idx.subtree(r)).collect();
+
+                let gone: Vec<EngineEvent> = idx.subtree(&path)
+                    .into_iter()
+                    .filter(|id| !kept.contains(id))
+                    .filter_map(|id| idx.get(id).map(|m| EngineEvent::Deleted { id, path: m.path.clone() }))
+                    .collect();
+
+                for event in &gone {
+                    if let EngineEvent::Deleted { id, .. } = event {
+                        idx.remove(*id);
+                        self.thumb_cache.invalidate(*id);
+                    }
+                }
+                gone
+            };
+
+            self.events.publish_all(&removed);
+            self.persist_index();
+        }
+
+        self.save_config()?;
+
+        Ok(())
+    }
+
+    pub fn list_roots(&self) -> Vec<RootInfo> {
+        let mut out: Vec<RootInfo> = self.roots
+            .read()
+            .values()
+            .map(|r| r.info())
+            .collect();
+
+        out.sort_by(|a, b| a.config.path.cmp(&b.config.path));
+        out
+    }
+
+    pub fn root_info(&self, path: &Path) -> Option<RootInfo> {
+        self.roots.read().get(path).map(|r| r.info())
+    }
+
+    fn start_root(&self, path: &Path, scan: bool) {
+        let Some(config) = self.roots.read().get(path).map(|r| r.info.config.clone()) else {
+            return;
+        };
+
+        if config.watch {
+            let poll_interval = config.poll_interval();
+            let watched = config
+                .filter(&self.global_exclude)
+                .and_then(|filter| {
+                    let watcher = start_watcher(
+                        config.path.clone(),
+                        self.changes.clone(),
+                        filter,
+                        self.path_lookup(),
+                        self.issue_handler(path),
+                        poll_interval
+                    )?;
+                    Ok(watcher)
+                });
+
+            match watched {
+                Ok(watcher) => {
+                    if let Some(root) = self.roots.write().get_mut(path) {
+                        root.watcher = Some(watcher);
+                        root.info.watching = true;
+                        root.info.polling = poll_interval.is_some();
+                    }
+                }
+                Err(e) => eprintln!("[engine] failed to watch {:?}: {e}", config.path)
+            }
+        }
+
+        if !scan {
+            let entries = self.index.read().subtree(path).len();
+            if let Some(root) = self.roots.write().get_mut(path) {
+                root.info.status = RootStatus::Ready;
+                root.info.entries = entries;
+            }
+            return;
+        }
+
+        self.root_scans().spawn(config);
+    }
+
+    fn root_scans(&self) -> RootScans {
+        RootScans {
+            index: self.index.clone(),
+            roots: self.roots.clone(),
+            store: self.store.clone(),
+            cache: self.thumb_cache.clone(),
+            events: self.events.clone(),
+            global_exclude: self.global_exclude.clone(),
+            jobs: self.jobs.clone()
+        }
+    }
+
+    /// What a root's watcher calls when it runs into trouble. Anything else
+    /// that knows events were missed, like a resume from sleep, may call it
+    /// too.
+    pub fn report_watch_issue(&self, root: &Path, issue: WatchIssue) {
+        (self.issue_handler(&absolute(root)))(issue);
+    }
+
+    fn scan_options_for(&self, path: &Path) -> ScanOptions {
+        scan_options_for(&self.roots, &self.global_exclude, path)
+    }
+
+    fn path_lookup(&self) -> PathLookup {
+        let index = self.index.clone();
+        Arc::new(move |path| index.read().id_for_path(path))
+    }
+
+    /// Turns watcher trouble on a root into a resync of that root, or into a
+    /// warning when it cannot be fixed from here.
+    fn issue_handler(&self, path: &Path) -> IssueHandler {
+        let path = path.to_path_buf();
+        let roots = self.roots.clone();
+        let scans = self.root_scans();
+
+        Arc::new(move |issue| match issue {
+            WatchIssue::Rescan => {
+                let Some(config) = roots.read().get(&path).map(|r| r.info.config.clone()) else {
+                    return;
+                };
+
+                // A burst of overflows asks for one scan, not one each.
+                if scans.spawn(config) {
+                    eprintln!("[engine] watcher lost events under {:?}, rescanning", path);
+                }
+            }
+            WatchIssue::WatchLimit => {
+                let warning = format!("watch limit reached, changes under {} may be missed until the limit is raised", path.display());
+
+                if let Some(root) = roots.write().get_mut(&path)
+                    && !root.info.warnings.contains(&warning)
+                {
+                    eprintln!("[engine] {warning}");
+                    root.info.warnings.push(warning);
+                }
+            }
+        })
+    }
+
+    fn save_config(&self) -> anyhow::Result<()> {
+        let mut roots: Vec<RootConfig> = self.roots
+            .read()
+            .values()
+            .map(|r| r.info.config.clone())
+            .collect();
+
+        roots.sort_by(|a, b| a.path.cmp(&b.path));
+
+        EngineConfig {
+            roots,
+            exclude: self.global_exclude.to_vec(),
+            debounce_ms: self.debounce.as_millis() as u64
+        }.save(&self.config_path)
     }
 
     pub fn shutdown(&self) {
         self.stop_flag.store(true, Ordering::Relaxed);
+        self.jobs.cancel_all();
+
+        for root in self.roots.write().values_mut() {
+            root.watcher = None;
+            root.info.watching = false;
+        }
 
         if let Some(handle) = self.watch_thread.write().take() {
             let _ = handle.join();
         }
 
-        *self.watcher.write() = None;
         self.thumb_worker.shutdown();
+        self.persist_index();
+    }
+
+    /// Registers a callback for index changes and finished thumbnails. It
+    /// runs on the engine's threads, so it should hand events off quickly.
+    pub fn subscribe(&self, subscriber: Subscriber) {
+        self.events.subscribe(subscriber);
     }
 
     pub fn search(&self, query: &str, limit: usize) -> Vec<FileMeta> {
         self.index.read().search(query, limit)
     }
 
+    pub fn search_with(&self, query: &str, limit: usize, mode: SearchMode) -> Result<Vec<SearchHit>, QueryError> {
+        let idx = self.index.read();
+
+        let metas = match mode {
+            SearchMode::Substring => idx.search(query, limit),
+            SearchMode::Fuzzy => return Ok(idx.search_fuzzy(query, limit)),
+            SearchMode::Query => idx.search_query(&Query::parse(query)?, limit)
+        };
+
+        Ok(metas.into_iter()
+            .map(|meta| SearchHit { meta, score: None, ranges: Vec::new() })
+            .collect())
+    }
+
     pub fn get_thumbnail(&self, id: FileId) -> Option<Vec<u8>> {
         self.thumb_cache
             .get(id)
             .map(|arc| arc.to_vec())
     }
 
+    pub fn get_thumbnails(&self, ids: &[FileId]) -> ThumbnailBatch {
+        let mut batch = ThumbnailBatch::default();
+
+        for &id in ids {
+            if let Some(bytes) = self.get_thumbnail(id) {
+                batch.ready.push((id, bytes));
+            } else if self.thumb_worker.is_pending(id) {
+                batch.pending.push(id);
+            } else {
+                batch.missing.push(id);
+            }
+        }
+
+        batch
+    }
+
+    /// Queues every id that has no thumbnail yet and reports where they all
+    /// stand, so a whole view can be filled from one call.
+    pub fn request_thumbnails(&self, ids: &[FileId], priority: ThumbnailPriority) -> ThumbnailBatch {
+        let metas: Vec<FileMeta> = {
+            let idx = self.index.read();
+            ids.iter().filter_map(|id| idx.get(*id).cloned()).collect()
+        };
+
+        for meta in metas {
+            if self.thumb_cache.get(meta.id).is_none() {
+                self.thumb_worker.submit_with(meta, priority);
+            }
+        }
+
+        self.get_thumbnails(ids)
+    }
+
     pub fn request_thumbnail(&self, id: FileId) -> bool {
         let meta = self.index.read().get(id).cloned();
 
@@ -110,30 +498,23 @@ impl EngineRuntime {
     }
 
     fn is_indexed(&self, path: &Path) -> bool {
-        self.index.read().files.values().any(|m| m.path.starts_with(path))
+        self.index.read().contains_path(path)
     }
 
     pub fn list_dir(&self, path: &Path) -> Vec<FileMeta> {
-        if !self.is_indexed(path) {
-            self.full_scan(path);
-        }
+        self.list_dir_with(path, &ListOptions::default())
+    }
 
-        let idx = self.index.read();
-        let base = normalize(path);
+    pub fn list_dir_with(&self, path: &Path, options: &ListOptions) -> Vec<FileMeta> {
+        let path = absolute(path);
 
-        let mut out: Vec<_> = idx.files
-            .values()
-            .filter(|m| {
-                m.path.parent()
-                    .map(normalize)
-                    .map(|p| p == base)
-                    .unwrap_or(false)
-            })
-            .cloned()
-            .collect();
+        if !self.is_indexed(&path) {
+            self.full_scan(&path);
+        }
 
-        out.sort_by_key(|m| (!matches!(m.kind, crate::models::FileKind::Directory), m.path.clone()));
-        out
+        let children: Vec<_> = self.index.read().children_of(&path).cloned().collect();
+
+        options.apply(children)
     }
 
     pub fn open_file(&self, path: &Path) -> anyhow::Result<()> {
@@ -158,6 +539,264 @@ impl EngineRuntime {
     }
 }
 
-fn normalize(p: &Path) -> PathBuf {
-    p.components().collect()
-}
\ No newline at end of file
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn spawn_watch_loop(
+    rx: Receiver<FsChange>,
+    index: Arc<RwLock<SimpleIndex>>,
+    cache: Arc<ThumbnailCache>,
+    worker: ThumbnailWorker,
+    events: Arc<EventBus>,
+    debounce: Duration,
+    stop: Arc<AtomicBool>
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let mut queue = ChangeQueue::new();
+
+        while !stop.load(Ordering::Relaxed) {
+            match rx.recv_timeout(Duration::from_millis(200)) {
+                Ok(change) => queue.push(change),
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => break
+            }
+
+            // The window starts with the first change, so a steady stream of
+            // events still gets applied every `debounce`.
+            let deadline = Instant::now() + debounce;
+            let mut disconnected = false;
+
+            while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+                match rx.recv_timeout(left) {
+                    Ok(change) => queue.push(change),
+                    Err(RecvTimeoutError::Timeout) => break,
+                    Err(RecvTimeoutError::Disconnected) => {
+                        disconnected = true;
+                        break;
+                    }
+                }
+            }
+
+            apply_batch(&index, &cache, &worker, &events, queue.drain());
+
+            if disconnected {
+                break;
+            }
+        }
+    })
+}
+
+fn apply_batch(index: &RwLock<SimpleIndex>, cache: &ThumbnailCache, worker: &ThumbnailWorker, events: &EventBus, batch: Vec<FsChange>) {
+    let published = apply_changes(&mut index.write(), &batch);
+    events.publish_all(&published);
+
+    // Before resubmitting, or the worker would find the old thumbnail.
+    invalidate_changed(cache, &batch);
+
+    let mut submitted = HashSet::new();
+
+    for change in batch {
+        if let FsChange::Created(id, meta) | FsChange::Modified(id, meta) = change
+            && !matches!(meta.kind, FileKind::Directory)
+            && submitted.insert(id)
+        {
+            worker.submit_with(meta, ThumbnailPriority::Low);
+        }
+    }
+}
+
+/// What scanning a configured root takes, so the watcher's issue handler
+/// can start one too.
+#[derive(Clone)]
+struct RootScans {
+    index: Arc<RwLock<SimpleIndex>>,
+    roots: Arc<RwLock<HashMap<PathBuf, RootState>>>,
+    store: Arc<IndexStore>,
+    cache: Arc<ThumbnailCache>,
+    events: Arc<EventBus>,
+    global_exclude: Arc<Vec<String>>,
+    jobs: Arc<ScanJobs>
+}
+
+impl RootScans {
+    /// Scans a configured root as a background job, unless it is already
+    /// being scanned. The root is marked as scanning before this returns.
+    fn spawn(&self, config: RootConfig) -> bool {
+        let job = {
+            let mut roots = self.roots.write();
+            let Some(root) = roots.get_mut(&config.path) else {
+                return false;
+            };
+            if root.scan.as_ref().is_some_and(|j| j.is_running()) {
+                return false;
+            }
+
+            let job = self.jobs.start(config.path.clone());
+            root.info.status = RootStatus::Scanning;
+            root.scan = Some(job.job());
+            job
+        };
+
+        let scans = self.clone();
+        thread::spawn(move || scans.run(config, job));
+        true
+    }
+
+    fn run(&self, config: RootConfig, job: RunningJob) {
+        let set_status = |status: RootStatus| {
+            if let Some(root) = self.roots.write().get_mut(&config.path) {
+                root.info.status = status;
+            }
+        };
+
+        let filter = match config.filter(&self.global_exclude) {
+            Ok(f) => f,
+            Err(e) => {
+                job.finish(JobState::Failed);
+                return set_status(RootStatus::Failed(e.to_string()));
+            }
+        };
+
+        let opts = ScanOptions { max_depth: config.max_depth, filter: Some(filter), follow_links: config.follow_links, progress: Some(job.progress.clone()) };
+        let metas = scan_root_with(&config.path, &opts);
+        let entries = metas.len();
+
+        // Cancelled, possibly by the root being removed.
+        if job.is_cancelled() {
+            if let Some(root) = self.roots.write().get_mut(&config.path) {
+                root.info.status = match root.info.last_scan {
+                    Some(_) => RootStatus::Ready,
+                    None => RootStatus::Pending
+                };
+            }
+            return job.finish(JobState::Cancelled);
+        }
+
+        apply_scan(&self.index, &self.cache, &self.events, &config.path, metas);
+
+        if let Some(root) = self.roots.write().get_mut(&config.path) {
+            root.info.status = RootStatus::Ready;
+            root.info.entries = entries;
+            root.info.last_scan = Some(SystemTime::now());
+        }
+
+        if let Err(e) = self.store.save(&self.index.read()) {
+            eprintln!("[engine] failed to persist index to {:?}: {e}", self.store.path());
+        }
+
+        job.finish(JobState::Completed);
+    }
+}
+
+fn apply_scan(index: &RwLock<SimpleIndex>, cache: &ThumbnailCache, events: &EventBus, root: &Path, metas: Vec<FileMeta>) -> Vec<FsChange> {
+    let (changes, published) = {
+        let mut idx = index.write();
+        let changes = idx.diff_scan(root, metas);
+        let published = apply_changes(&mut idx, &changes);
+        (changes, published)
+    };
+    events.publish_all(&published);
+    invalidate_changed(cache, &changes);
+
+    changes
+}
+
+/// Drops the thumbnails of files whose contents changed or that are gone.
+/// A modification that only moved the file keeps its thumbnail.
+fn invalidate_changed(cache: &ThumbnailCache, changes: &[FsChange]) {
+    for change in changes {
+        match change {
+            FsChange::Modified(id, meta) if !meta.has_thumbnail => cache.invalidate(*id),
+            FsChange::Deleted(id) => cache.invalidate(*id),
+            _ => {}
+        }
+    }
+}
+
+/// Applies `changes` in order and describes each one for subscribers; a
+/// deletion has to be described before its path is gone from the index.
+fn apply_changes(idx: &mut SimpleIndex, changes: &[FsChange]) -> Vec<EngineEvent> {
+    let mut out = Vec::with_capacity(changes.len());
+
+    for change in changes {
+        let event = match change {
+            FsChange::Created(_, meta) => Some(EngineEvent::Created(meta.clone())),
+            FsChange::Modified(_, meta) => Some(EngineEvent::Modified(meta.clone())),
+            FsChange::Deleted(id) => idx.get(*id).map(|m| EngineEvent::Deleted { id: *id, path: m.path.clone() }),
+            FsChange::Renamed { id, from, to } => Some(EngineEvent::Renamed { id: *id, from: from.clone(), to: to.clone() })
+        };
+
+        idx.apply(change);
+        out.extend(event);
+    }
+
+    out
+}
+
+/// Re-stats every entry of a warm-started index and only refreshes the ones
+/// whose size or mtime drifted while the daemon was not running.
+/// Scans below a configured root follow that root's rules; anything else
+/// still gets the global excludes and ignore files.
+fn scan_options_for(roots: &RwLock<HashMap<PathBuf, RootState>>, global_exclude: &[String], path: &Path) -> ScanOptions {
+    let config = roots
+        .read()
+        .values()
+        .map(|r| &r.info.config)
+        .filter(|c| path.starts_with(&c.path))
+        .max_by_key(|c| c.path.components().count())
+        .cloned()
+        .unwrap_or_else(|| RootConfig::new(path.to_path_buf()));
+
+    let filter = match config.filter(global_exclude) {
+        Ok(f) => f,
+        Err(e) => {
+            eprintln!("[engine] invalid exclude patterns for {:?}: {e}", config.path);
+            return ScanOptions::default();
+        }
+    };
+
+    ScanOptions { max_depth: None, filter: Some(filter), follow_links: config.follow_links, progress: None }
+}
+
+/// Returns the outermost directories that changed, whose contents still
+/// need a scan.
+fn reconcile(index: &RwLock<SimpleIndex>, cache: &ThumbnailCache) -> Vec<PathBuf> {
+    let snapshot: Vec<(FileId, PathBuf, u64, Option<SystemTime>)> = index
+        .read()
+        .iter()
+        .map(|m| (m.id, m.path.clone(), m.size, m.modified))
+        .collect();
+
+    let stale: Vec<(FileId, Option<FileMeta>)> = snapshot
+        .into_par_iter()
+        .filter_map(|(id, path, size, modified)| {
+            match read_metadata(&path) {
+                Some(meta) if meta.id == id && meta.size == size && meta.modified == modified => None,
+                fresh => Some((id, fresh))
+            }
+        })
+        .collect();
+
+    let mut drifted: Vec<PathBuf> = stale
+        .iter()
+        .filter_map(|(_, fresh)| fresh.as_ref())
+        .filter(|m| matches!(m.kind, FileKind::Directory))
+        .map(|m| m.path.clone())
+        .collect();
+    drifted.sort();
+    drifted.dedup_by(|inner, outer| inner.starts_with(outer));
+
+    let mut idx = index.write();
+    for (id, fresh) in stale {
+        cache.invalidate(id);
+        idx.remove(id);
+
+        if let Some(meta) = fresh {
+            cache.invalidate(meta.id);
+            idx.insert(meta);
+        }
+    }
+
+    drifted
+}
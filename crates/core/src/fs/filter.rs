@@ -0,0 +1,142 @@
+use std::{collections::HashMap, path::{Path, PathBuf}, sync::Arc};
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use parking_lot::RwLock;
+
+use crate::fs::metadata::is_hidden;
+
+pub const IGNORE_FILES: [&str; 2] = [".gitignore", ".lunioignore"];
+
+type RulesCache = HashMap<PathBuf, Option<Arc<Gitignore>>>;
+
+/// Decides which paths below a root are left out of the index.
+///
+/// Exclude patterns containing a `/` are matched against the path relative
+/// to the root, bare patterns such as `node_modules` against every file
+/// name. On top of that, `.gitignore` and `.lunioignore` files found along
+/// the way are honored, the deepest one winning as in git. Hidden entries
+/// can be left out as a whole.
+#[derive(Debug, Clone)]
+pub struct PathFilter {
+    root: PathBuf,
+    names: GlobSet,
+    paths: GlobSet,
+    ignore_files: Option<Arc<RwLock<RulesCache>>>,
+    include_hidden: bool
+}
+
+impl PathFilter {
+    pub fn new(root: &Path, exclude: &[String], ignore_files: bool, include_hidden: bool) -> anyhow::Result<Self> {
+        let mut names = GlobSetBuilder::new();
+        let mut paths = GlobSetBuilder::new();
+
+        for pattern in exclude {
+            let trimmed = pattern.trim_end_matches('/');
+
+            if trimmed.contains('/') {
+                paths.add(Glob::new(trimmed.trim_start_matches('/'))?);
+            } else {
+                names.add(Glob::new(trimmed)?);
+            }
+        }
+
+        Ok(Self {
+            root: root.to_path_buf(),
+            names: names.build()?,
+            paths: paths.build()?,
+            ignore_files: ignore_files.then(Default::default),
+            include_hidden
+        })
+    }
+
+    pub fn is_excluded(&self, path: &Path, is_dir: bool) -> bool {
+        let Ok(rel) = path.strip_prefix(&self.root) else {
+            return false;
+        };
+
+        if rel.as_os_str().is_empty() {
+            return false;
+        }
+
+        if !self.include_hidden && rel.ancestors().any(|p| !p.as_os_str().is_empty() && is_hidden(&self.root.join(p))) {
+            return true;
+        }
+
+        if !self.names.is_empty() && rel.iter().any(|c| self.names.is_match(c)) {
+            return true;
+        }
+
+        if !self.paths.is_empty() && rel.ancestors().any(|p| !p.as_os_str().is_empty() && self.paths.is_match(p)) {
+            return true;
+        }
+
+        self.is_ignored(path, is_dir)
+    }
+
+    /// Drops the cached rules of `dir` after one of its ignore files changed.
+    pub fn invalidate(&self, dir: &Path) {
+        if let Some(cache) = &self.ignore_files {
+            cache.write().remove(dir);
+        }
+    }
+
+    fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        if self.ignore_files.is_none() {
+            return false;
+        }
+
+        let Some(parent) = path.parent() else {
+            return false;
+        };
+
+        for dir in parent.ancestors() {
+            if let Some(rules) = self.rules_for(dir) {
+                let m = rules.matched_path_or_any_parents(path, is_dir);
+
+                if m.is_ignore() {
+                    return true;
+                }
+                if m.is_whitelist() {
+                    return false;
+                }
+            }
+
+            if dir == self.root {
+                break;
+            }
+        }
+
+        false
+    }
+
+    fn rules_for(&self, dir: &Path) -> Option<Arc<Gitignore>> {
+        let cache = self.ignore_files.as_ref()?;
+
+        if let Some(cached) = cache.read().get(dir) {
+            return cached.clone();
+        }
+
+        let mut builder = GitignoreBuilder::new(dir);
+        let mut found = false;
+
+        for name in IGNORE_FILES {
+            let file = dir.join(name);
+            if file.is_file() {
+                found = true;
+                if let Some(e) = builder.add(&file) {
+                    eprintln!("[filter] problem reading {:?}: {e}", file);
+                }
+            }
+        }
+
+        let rules = if found {
+            builder.build().ok().map(Arc::new)
+        } else {
+            None
+        };
+
+        cache.write().insert(dir.to_path_buf(), rules.clone());
+        rules
+    }
+}
@@ -6,7 +6,7 @@ use crate::models::FileId;
 #[inline]
 pub fn generate_file_id(path: &Path) -> Option<FileId> {
     use std::{fs, os::unix::fs::MetadataExt};
-    let meta = match fs::metadata(path) {
+    let meta = match fs::symlink_metadata(path) {
         Ok(m) => m,
         Err(_) => return None
     };
@@ -21,9 +21,8 @@ pub fn generate_file_id(path: &Path) -> Option<FileId> {
 #[inline]
 pub fn generate_file_id(path: &Path) -> Option<FileId> {
     use std::hash::{Hash, Hasher};
-    use file_id::get_high_res_file_id;
 
-    let fid = match get_high_res_file_id(path) {
+    let fid = match high_res_id_no_follow(path) {
         Ok(v) => v,
         Err(_) => return None
     };
@@ -40,6 +39,36 @@ pub fn generate_file_id(path: &Path) -> Option<FileId> {
     Some(FileId(((h1 as u128) << 64) | (h2 as u128)))
 }
 
+/// `file_id::get_high_res_file_id`, but a link is opened itself rather than
+/// its target, so it gets its own id as it does through `symlink_metadata`
+/// on unix.
+#[cfg(windows)]
+fn high_res_id_no_follow(path: &Path) -> std::io::Result<file_id::FileId> {
+    use std::{fs, io, mem, os::windows::{fs::OpenOptionsExt, io::AsRawHandle}};
+    use windows_sys::Win32::{Foundation::HANDLE, Storage::FileSystem::{FILE_FLAG_BACKUP_SEMANTICS, FILE_FLAG_OPEN_REPARSE_POINT, FILE_ID_INFO, FileIdInfo, GetFileInformationByHandleEx}};
+
+    let file = fs::OpenOptions::new()
+        .access_mode(0)
+        .custom_flags(FILE_FLAG_BACKUP_SEMANTICS | FILE_FLAG_OPEN_REPARSE_POINT)
+        .open(path)?;
+
+    let mut info: FILE_ID_INFO = unsafe { mem::zeroed() };
+    let ret = unsafe {
+        GetFileInformationByHandleEx(
+            file.as_raw_handle() as HANDLE,
+            FileIdInfo,
+            &mut info as *mut FILE_ID_INFO as _,
+            mem::size_of::<FILE_ID_INFO>() as u32
+        )
+    };
+
+    if ret == 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(file_id::FileId::new_high_res(info.VolumeSerialNumber, u128::from_le_bytes(info.FileId.Identifier)))
+}
+
 #[cfg(not(any(unix, windows)))]
 #[inline]
 pub fn generate_file_id(path: &Path) -> Option<FileId> {
@@ -1,9 +1,15 @@
-use std::{fs, path::Path};
+use std::{fs::{self, Metadata}, path::Path};
 
-use crate::{METADTA_VERSION, fs::id::generate_file_id, models::FileMeta};
+use crate::{METADTA_VERSION, fs::id::generate_file_id, models::{FileKind, FileMeta}};
 
 pub fn read_metadata(path: &Path) -> Option<FileMeta> {
-    let meta = fs::metadata(path).ok()?;
+    let meta = fs::symlink_metadata(path).ok()?;
+    build_metadata(path, &meta)
+}
+
+/// Builds the index entry for `path` from its `lstat` metadata, so that
+/// symlinks are recorded as links rather than as their targets.
+pub fn build_metadata(path: &Path, meta: &Metadata) -> Option<FileMeta> {
     let id = generate_file_id(path)?;
 
     Some(FileMeta {
@@ -11,13 +17,48 @@ pub fn read_metadata(path: &Path) -> Option<FileMeta> {
         id,
         path: path.to_path_buf(),
         size: meta.len(),
-        kind: if meta.is_dir() {
-            crate::models::FileKind::Directory
-        } else {
-            crate::models::FileKind::File
-        },
+        kind: file_kind(path, meta),
         modified: meta.modified().ok(),
         created: meta.created().ok(),
         has_thumbnail: false
     })
-}
\ No newline at end of file
+}
+
+fn file_kind(path: &Path, meta: &Metadata) -> FileKind {
+    let ft = meta.file_type();
+
+    if ft.is_symlink() {
+        FileKind::Symlink {
+            target: fs::read_link(path).unwrap_or_default(),
+            broken: fs::metadata(path).is_err()
+        }
+    } else if ft.is_dir() {
+        FileKind::Directory
+    } else if ft.is_file() {
+        FileKind::File
+    } else {
+        FileKind::Other
+    }
+}
+
+#[cfg(windows)]
+pub fn is_hidden(path: &Path) -> bool {
+    use std::os::windows::fs::MetadataExt;
+
+    const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+
+    is_dotfile(path) || fs::symlink_metadata(path)
+        .map(|m| m.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(windows))]
+pub fn is_hidden(path: &Path) -> bool {
+    is_dotfile(path)
+}
+
+fn is_dotfile(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|n| n.starts_with('.'))
+}
@@ -1,4 +1,6 @@
 pub mod metadata;
 pub mod scan;
 pub mod watcher;
-pub mod id;
\ No newline at end of file
+pub mod id;
+pub mod filter;
+pub mod mounts;
\ No newline at end of file
@@ -0,0 +1,77 @@
+use std::path::{Path, PathBuf};
+
+/// File systems whose changes are not reliably reported through inotify,
+/// typically because they can change on another machine.
+const REMOTE_FS: [&str; 11] = ["nfs", "nfs4", "cifs", "smb3", "smbfs", "9p", "afs", "ceph", "glusterfs", "sshfs", "davfs"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mount {
+    pub mount_point: PathBuf,
+    pub fs_type: String
+}
+
+impl Mount {
+    pub fn needs_polling(&self) -> bool {
+        let fs_type = self.fs_type.as_str();
+
+        // FUSE mounts show up as `fuse.<name>`. `fuseblk` is a local disk
+        // (ntfs-3g, exfat) where every change goes through this kernel.
+        REMOTE_FS.contains(&fs_type) || fs_type == "fuse" || fs_type.starts_with("fuse.")
+    }
+}
+
+/// Parses the contents of `/proc/self/mountinfo`.
+pub fn parse_mountinfo(text: &str) -> Vec<Mount> {
+    text.lines()
+        .filter_map(|line| {
+            let (left, right) = line.split_once(" - ")?;
+            let mount_point = left.split(' ').nth(4)?;
+            let fs_type = right.split(' ').next()?;
+
+            Some(Mount {
+                mount_point: PathBuf::from(unescape(mount_point)),
+                fs_type: fs_type.to_string()
+            })
+        })
+        .collect()
+}
+
+/// The mount `path` lives on, i.e. the one with the longest matching mount
+/// point. Always `None` outside Linux.
+pub fn mount_of(path: &Path) -> Option<Mount> {
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+
+    let text = std::fs::read_to_string("/proc/self/mountinfo").ok()?;
+    let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+
+    parse_mountinfo(&text)
+        .into_iter()
+        .filter(|m| path.starts_with(&m.mount_point))
+        .max_by_key(|m| m.mount_point.components().count())
+}
+
+/// Undoes the octal escapes (`\040` for a space) used in mountinfo paths.
+fn unescape(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let code = bytes.get(i + 1..i + 4)
+            .and_then(|d| std::str::from_utf8(d).ok())
+            .and_then(|d| u8::from_str_radix(d, 8).ok());
+
+        if bytes[i] == b'\\' && let Some(code) = code {
+            out.push(code);
+            i += 4;
+            continue;
+        }
+
+        out.push(bytes[i]);
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&out).into_owned()
+}
@@ -1,41 +1,107 @@
-use std::{path::Path};
+use std::{collections::HashSet, fs, path::Path, sync::{Arc, atomic::{AtomicBool, AtomicUsize, Ordering}}};
 use rayon::iter::{ParallelBridge, ParallelIterator};
-use walkdir::{DirEntry, WalkDir};
+use walkdir::WalkDir;
 
-use crate::{METADTA_VERSION, fs::id::generate_file_id, models::FileMeta};
+use crate::{fs::{filter::PathFilter, id::generate_file_id, metadata::build_metadata}, models::{FileId, FileMeta}};
 
-#[inline]
-fn is_valid(entry: &DirEntry) -> bool {
-    let ft = entry.file_type();
-    ft.is_file() | ft.is_dir()
+#[derive(Debug, Clone, Default)]
+pub struct ScanOptions {
+    pub max_depth: Option<usize>,
+    pub filter: Option<PathFilter>,
+    pub follow_links: bool,
+    pub progress: Option<Arc<ScanProgress>>
+}
+
+/// Counters a running walk keeps up to date, plus the flag that stops it.
+#[derive(Debug, Default)]
+pub struct ScanProgress {
+    pub dirs: AtomicUsize,
+    pub files: AtomicUsize,
+    pub errors: AtomicUsize,
+    cancelled: AtomicBool
+}
+
+impl ScanProgress {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
 }
 
 pub fn scan_root<P: AsRef<Path>>(root: P) -> Vec<FileMeta> {
-    WalkDir::new(root)
-        .follow_links(false)
+    scan_root_with(root, &ScanOptions::default())
+}
+
+pub fn scan_root_with<P: AsRef<Path>>(root: P, opts: &ScanOptions) -> Vec<FileMeta> {
+    let root = root.as_ref();
+    let mut walker = WalkDir::new(root).follow_links(opts.follow_links);
+
+    if let Some(depth) = opts.max_depth {
+        walker = walker.max_depth(depth);
+    }
+
+    let progress = opts.progress.clone().unwrap_or_default();
+
+    // walkdir only catches links back into an ancestor; this also stops the
+    // same directory from being walked twice through different links.
+    let mut visited: HashSet<FileId> = HashSet::new();
+    let real_root = fs::canonicalize(root).ok();
+
+    let mut entries = walker
         .into_iter()
-        .filter_map(|e| e.ok())
-        .filter(|e| is_valid(e))
+        .filter_entry(|e| !opts.filter.as_ref().is_some_and(|f| f.is_excluded(e.path(), e.file_type().is_dir())));
+
+    // Directories are yielded either way but only walked the first time. A
+    // link to a directory inside the root is never walked, so its target is
+    // found under its real path whichever one readdir lists first.
+    let walked = std::iter::from_fn(move || {
+        let entry = entries.next()?;
+
+        if let Ok(e) = &entry
+            && opts.follow_links
+            && e.depth() > 0
+            && e.file_type().is_dir()
+        {
+            let first_visit = match e.path_is_symlink() {
+                true => fs::canonicalize(e.path()).ok()
+                    .filter(|target| !real_root.as_ref().is_some_and(|r| target.starts_with(r)))
+                    .and_then(|target| generate_file_id(&target))
+                    .is_some_and(|id| visited.insert(id)),
+                false => generate_file_id(e.path()).is_none_or(|id| visited.insert(id))
+            };
+
+            if !first_visit {
+                entries.skip_current_dir();
+            }
+        }
+
+        Some(entry)
+    });
+
+    walked
+        .take_while(|_| !progress.is_cancelled())
+        .filter_map(|e| {
+            if e.is_err() {
+                progress.errors.fetch_add(1, Ordering::Relaxed);
+            }
+            e.ok()
+        })
         .par_bridge()
         .filter_map(|entry| {
-            let path = entry.path().to_path_buf();
-            let metadata = entry.metadata().ok()?;
-            let id = generate_file_id(&path)?;
-
-            Some(FileMeta {
-                version: METADTA_VERSION,
-                id,
-                path,
-                size: metadata.len(),
-                kind: if metadata.is_dir() {
-                    crate::models::FileKind::Directory
-                } else {
-                    crate::models::FileKind::File
-                },
-                modified: metadata.modified().ok(),
-                created: metadata.created().ok(),
-                has_thumbnail: false
-            })
+            let path = entry.path();
+            let meta = fs::symlink_metadata(path).ok().and_then(|m| build_metadata(path, &m));
+
+            let counter = match &meta {
+                None => &progress.errors,
+                Some(_) if entry.file_type().is_dir() => &progress.dirs,
+                Some(_) => &progress.files
+            };
+            counter.fetch_add(1, Ordering::Relaxed);
+
+            meta
         })
         .collect()
-}
\ No newline at end of file
+}
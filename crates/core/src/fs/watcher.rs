@@ -1,66 +1,343 @@
-use std::{path::PathBuf, sync::mpsc::{Receiver, channel}};
-use notify::{Config, RecommendedWatcher, RecursiveMode, Result as NotifyResult, Watcher, Event};
+use std::{collections::HashMap, path::{Path, PathBuf}, sync::{Arc, Weak, mpsc::{Receiver, Sender, channel}}, thread, time::{Duration, Instant}};
+use notify::{Config, ErrorKind, PollWatcher, RecommendedWatcher, RecursiveMode, Result as NotifyResult, Watcher, Event, EventKind, event::{ModifyKind, RenameMode}};
+use parking_lot::Mutex;
 
-use crate::{fs::{id::generate_file_id, metadata::read_metadata}, models::{FileId, FileMeta}};
+use crate::{fs::{filter::{IGNORE_FILES, PathFilter}, id::generate_file_id, metadata::read_metadata, scan::{ScanOptions, scan_root_with}}, models::{FileId, FileKind, FileMeta}};
+
+/// How long the source half of a rename waits for its destination before it
+/// is treated as a move out of the watched tree.
+const RENAME_TIMEOUT: Duration = Duration::from_millis(500);
 
 #[derive(Debug, Clone)]
 pub enum FsChange {
     Created(FileId, FileMeta),
     Modified(FileId, FileMeta),
-    Deleted(FileId)
+    Deleted(FileId),
+    Renamed { id: FileId, from: PathBuf, to: PathBuf }
+}
+
+impl FsChange {
+    pub fn id(&self) -> FileId {
+        match self {
+            FsChange::Created(id, _) | FsChange::Modified(id, _) | FsChange::Deleted(id) => *id,
+            FsChange::Renamed { id, .. } => *id
+        }
+    }
 }
 
-pub struct FsWatcher {
-    pub rx: Receiver<FsChange>,
-    pub watcher: RecommendedWatcher
+/// Resolves a path to the id it is indexed under. Removed paths can no
+/// longer be stat'ed, so deletions and renames are resolved through this.
+pub type PathLookup = Arc<dyn Fn(&Path) -> Option<FileId> + Send + Sync>;
+
+/// Problems after which the index can no longer trust the events it got.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchIssue {
+    /// Events were dropped, e.g. the inotify queue overflowed.
+    Rescan,
+    /// The OS refused further watches, so part of the tree is not covered.
+    WatchLimit
 }
 
-pub fn start_watcher(root: PathBuf) -> NotifyResult<FsWatcher> {
-    let (tx, rx) = channel::<FsChange>();
+pub type IssueHandler = Arc<dyn Fn(WatchIssue) + Send + Sync>;
+
+struct PendingRename {
+    tracker: Option<usize>,
+    id: FileId,
+    from: PathBuf,
+    at: Instant
+}
+
+struct WatchState {
+    root: PathBuf,
+    tx: Sender<FsChange>,
+    filter: PathFilter,
+    lookup: PathLookup,
+    /// Paths sent as created or renamed that the index may not have caught
+    /// up with yet, since changes are only applied after the debounce window.
+    recent: HashMap<PathBuf, FileId>,
+    /// Directories that arrived whole, for the subtree scanner.
+    arrived: Sender<PathBuf>,
+    pending: Option<PendingRename>,
+    paired: Option<usize>
+}
+
+pub fn start_watcher(
+    root: PathBuf,
+    tx: Sender<FsChange>,
+    filter: PathFilter,
+    lookup: PathLookup,
+    on_issue: IssueHandler,
+    poll_interval: Option<Duration>
+) -> NotifyResult<Box<dyn Watcher + Send + Sync>> {
+    let (arrived, to_scan) = channel();
+    let state = Arc::new(Mutex::new(WatchState {
+        root: root.clone(),
+        tx,
+        filter: filter.clone(),
+        lookup,
+        recent: HashMap::new(),
+        arrived,
+        pending: None,
+        paired: None
+    }));
+
+    spawn_rename_flusher(Arc::downgrade(&state));
+    spawn_subtree_scanner(Arc::downgrade(&state), to_scan, filter);
 
+    let report = on_issue.clone();
     let callback = move |res: NotifyResult<Event>| {
         let event = match res {
             Ok(e) => e,
+            Err(err) if matches!(err.kind, ErrorKind::MaxFilesWatch) => {
+                return report(WatchIssue::WatchLimit);
+            }
             Err(err) => {
                 eprintln!("[watcher] Error: {:?}", err);
                 return;
             }
         };
 
-        match event.kind {
-            notify::EventKind::Create(_) |
-            notify::EventKind::Modify(_) |
-            notify::EventKind::Remove(_) => {},
-            _ => return
+        if event.need_rescan() {
+            return report(WatchIssue::Rescan);
+        }
+
+        state.lock().handle(event);
+    };
+
+    let mut watcher: Box<dyn Watcher + Send + Sync> = match poll_interval {
+        Some(interval) => Box::new(PollWatcher::new(callback, Config::default().with_poll_interval(interval))?),
+        None => Box::new(RecommendedWatcher::new(callback, Config::default())?)
+    };
+
+    // Running out of watches part way still leaves the rest covered.
+    match watcher.watch(&root, RecursiveMode::Recursive) {
+        Err(e) if matches!(e.kind, ErrorKind::MaxFilesWatch) => on_issue(WatchIssue::WatchLimit),
+        res => res?
+    }
+
+    Ok(watcher)
+}
+
+/// A rename source with no destination never gets a follow-up event, so it
+/// is flushed from here. The thread ends once the watcher is dropped.
+fn spawn_rename_flusher(state: Weak<Mutex<WatchState>>) {
+    thread::spawn(move || {
+        while let Some(state) = state.upgrade() {
+            let mut state = state.lock();
+            state.flush_pending(false);
+            state.prune_recent();
+            drop(state);
+            thread::sleep(RENAME_TIMEOUT / 2);
         }
+    });
+}
+
+/// Scans directories that arrived from outside the tree. Doing it in the
+/// notify callback would hold every other event up behind the scan. The
+/// thread ends once the watcher is dropped.
+fn spawn_subtree_scanner(state: Weak<Mutex<WatchState>>, to_scan: Receiver<PathBuf>, filter: PathFilter) {
+    thread::spawn(move || {
+        let opts = ScanOptions { filter: Some(filter), ..Default::default() };
 
-        for path in event.paths {
-            if matches!(event.kind, notify::EventKind::Remove(_)) {
-                let id = match generate_file_id(&path) {
-                    Some(id) => id,
-                    None => continue
-                };
-                let _ = tx.send(FsChange::Deleted(id));
-                continue;
+        for dir in to_scan {
+            let metas = scan_root_with(&dir, &opts);
+            let Some(state) = state.upgrade() else { return };
+            let mut state = state.lock();
+
+            // Whatever went away during the scan had its event already, and
+            // that found nothing to delete.
+            for meta in metas.into_iter().filter(|m| m.path.symlink_metadata().is_ok()) {
+                state.created(meta);
+            }
+        }
+    });
+}
+
+impl WatchState {
+    fn handle(&mut self, event: Event) {
+        let tracker = event.tracker();
+
+        match event.kind {
+            EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+                self.flush_pending(true);
+                if let Some(from) = event.paths.into_iter().next() {
+                    self.rename_from(tracker, from);
+                }
             }
-            
-            let id = match generate_file_id(&path) {
-                Some(id) => id,
-                None => continue
-            };
-
-            if let Some(meta) = read_metadata(&path) {
-                if matches!(event.kind, notify::EventKind::Create(_)) {
-                    let _ = tx.send(FsChange::Created(id, meta));
-                } else {
-                    let _ = tx.send(FsChange::Modified(id, meta));
+            EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+                if let Some(to) = event.paths.into_iter().next() {
+                    self.rename_to(tracker, to);
                 }
             }
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+                if tracker.is_some() && tracker == self.paired.take() {
+                    return;
+                }
+
+                self.flush_pending(true);
+                if let [from, to] = &event.paths[..] {
+                    match self.forget(from) {
+                        Some(id) => self.renamed(id, from.clone(), to.clone()),
+                        None => self.appeared(to)
+                    }
+                }
+            }
+            EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_) => {
+                self.flush_pending(true);
+                for path in &event.paths {
+                    self.changed(path, &event.kind);
+                }
+            }
+            _ => {}
         }
-    };
+    }
+
+    fn rename_from(&mut self, tracker: Option<usize>, from: PathBuf) {
+        self.invalidate_rules(&from);
+
+        if let Some(id) = self.forget(&from) {
+            self.pending = Some(PendingRename { tracker, id, from, at: Instant::now() });
+        }
+    }
 
-    let mut watcher = RecommendedWatcher::new(callback, Config::default())?;
-    watcher.watch(&root, RecursiveMode::Recursive)?;
+    fn rename_to(&mut self, tracker: Option<usize>, to: PathBuf) {
+        // Backends without cookies report both halves back to back.
+        let matched = self.pending
+            .take_if(|p| p.tracker == tracker || p.tracker.is_none());
 
-    Ok(FsWatcher { rx, watcher })
-}
\ No newline at end of file
+        match matched {
+            Some(p) => {
+                self.paired = tracker;
+                self.renamed(p.id, p.from, to);
+            }
+            None => {
+                self.paired = None;
+                self.appeared(&to);
+            }
+        }
+    }
+
+    fn renamed(&mut self, id: FileId, from: PathBuf, to: PathBuf) {
+        self.invalidate_rules(&to);
+
+        if self.filter.is_excluded(&to, to.is_dir()) {
+            self.forget_below(&from);
+            let _ = self.tx.send(FsChange::Deleted(id));
+            return;
+        }
+
+        self.move_below(&from, &to);
+        self.recent.insert(to.clone(), id);
+        let _ = self.tx.send(FsChange::Renamed { id, from, to });
+    }
+
+    /// Something arrived from outside the watched tree; a directory comes
+    /// with everything below it and no events of its own, so it is handed to
+    /// the subtree scanner.
+    fn appeared(&mut self, path: &Path) {
+        self.invalidate_rules(path);
+
+        if !path.starts_with(&self.root) || self.filter.is_excluded(path, path.is_dir()) {
+            return;
+        }
+
+        let Some(meta) = read_metadata(path) else { return };
+
+        if !matches!(meta.kind, FileKind::Directory) {
+            return self.created(meta);
+        }
+
+        let _ = self.arrived.send(path.to_path_buf());
+    }
+
+    fn changed(&mut self, path: &Path, kind: &EventKind) {
+        self.invalidate_rules(path);
+
+        // Backends that only say "renamed" leave it to us to find out which
+        // side of the rename this path is.
+        let gone = matches!(kind, EventKind::Remove(_))
+            || (matches!(kind, EventKind::Modify(ModifyKind::Name(_))) && !path.exists());
+
+        if gone {
+            if let Some(id) = self.forget(path) {
+                let _ = self.tx.send(FsChange::Deleted(id));
+            }
+            self.forget_below(path);
+            return;
+        }
+
+        if self.filter.is_excluded(path, path.is_dir()) {
+            return;
+        }
+
+        let id = match generate_file_id(path) {
+            Some(id) => id,
+            None => return
+        };
+
+        if let Some(meta) = read_metadata(path) {
+            self.recent.insert(path.to_path_buf(), id);
+
+            if matches!(kind, EventKind::Create(_)) {
+                let _ = self.tx.send(FsChange::Created(id, meta));
+            } else {
+                let _ = self.tx.send(FsChange::Modified(id, meta));
+            }
+        }
+    }
+
+    fn created(&mut self, meta: FileMeta) {
+        self.recent.insert(meta.path.clone(), meta.id);
+        let _ = self.tx.send(FsChange::Created(meta.id, meta));
+    }
+
+    /// Resolves a path that is going away. A change sent moments ago wins
+    /// over the index, which may still have an older file there or nothing
+    /// at all.
+    fn forget(&mut self, path: &Path) -> Option<FileId> {
+        self.recent.remove(path).or_else(|| (self.lookup)(path))
+    }
+
+    fn forget_below(&mut self, dir: &Path) {
+        self.recent.retain(|p, _| !p.starts_with(dir));
+    }
+
+    fn move_below(&mut self, from: &Path, to: &Path) {
+        let moved: Vec<(PathBuf, FileId)> = self.recent
+            .extract_if(|p, _| p.starts_with(from))
+            .filter_map(|(p, id)| Some((to.join(p.strip_prefix(from).ok()?), id)))
+            .collect();
+
+        self.recent.extend(moved);
+    }
+
+    /// Drops the paths the index now resolves by itself.
+    fn prune_recent(&mut self) {
+        let lookup = &self.lookup;
+        self.recent.retain(|path, id| lookup(path) != Some(*id));
+    }
+
+    /// Emits the pending rename source as a deletion, either right away or
+    /// once it has waited long enough for its destination.
+    fn flush_pending(&mut self, now: bool) {
+        let expired = self.pending
+            .take_if(|p| now || p.at.elapsed() >= RENAME_TIMEOUT);
+
+        if let Some(p) = expired {
+            self.forget_below(&p.from);
+            let _ = self.tx.send(FsChange::Deleted(p.id));
+        }
+    }
+
+    fn invalidate_rules(&self, path: &Path) {
+        if is_ignore_file(path) && let Some(dir) = path.parent() {
+            self.filter.invalidate(dir);
+        }
+    }
+}
+
+fn is_ignore_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|n| IGNORE_FILES.contains(&n))
+}
@@ -0,0 +1,131 @@
+use std::{path::Path, time::{Duration, SystemTime}};
+
+const SCORE_MATCH: i64 = 16;
+const BONUS_CONSECUTIVE: i64 = 24;
+const BONUS_BOUNDARY: i64 = 30;
+const BONUS_FIRST_CHAR: i64 = 15;
+const PENALTY_GAP: i64 = 3;
+const PENALTY_LEADING: i64 = 1;
+const MAX_LEADING_PENALTY: i64 = 15;
+const PENALTY_DEPTH: i64 = 2;
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuzzyMatch {
+    pub score: i64,
+    pub ranges: Vec<(usize, usize)>
+}
+
+/// Matches `query` as a subsequence of `name`, in the style of fzf.
+///
+/// The first complete match is found forwards and then tightened backwards,
+/// like fzf's v1 algorithm. Returned ranges are half-open char offsets into
+/// `name`.
+pub fn fuzzy_match(query: &str, name: &str) -> Option<FuzzyMatch> {
+    let needle: Vec<char> = query.chars().map(fold).collect();
+    if needle.is_empty() {
+        return None;
+    }
+
+    let hay: Vec<char> = name.chars().collect();
+    let lower: Vec<char> = hay.iter().copied().map(fold).collect();
+
+    let mut qi = 0;
+    let mut end = 0;
+    for (i, c) in lower.iter().enumerate() {
+        if *c == needle[qi] {
+            qi += 1;
+            if qi == needle.len() {
+                end = i;
+                break;
+            }
+        }
+    }
+
+    if qi < needle.len() {
+        return None;
+    }
+
+    let mut positions = vec![0; needle.len()];
+    let mut qi = needle.len();
+    for i in (0..=end).rev() {
+        if lower[i] == needle[qi - 1] {
+            qi -= 1;
+            positions[qi] = i;
+            if qi == 0 {
+                break;
+            }
+        }
+    }
+
+    let mut score = 0;
+    let mut prev: Option<usize> = None;
+
+    for &pos in &positions {
+        score += SCORE_MATCH;
+
+        if is_boundary(&hay, pos) {
+            score += BONUS_BOUNDARY;
+        }
+
+        match prev {
+            Some(p) if p + 1 == pos => score += BONUS_CONSECUTIVE,
+            Some(p) => score -= PENALTY_GAP * (pos - p - 1) as i64,
+            None if pos == 0 => score += BONUS_FIRST_CHAR,
+            None => score -= (PENALTY_LEADING * pos as i64).min(MAX_LEADING_PENALTY)
+        }
+
+        prev = Some(pos);
+    }
+
+    Some(FuzzyMatch { score, ranges: to_ranges(&positions) })
+}
+
+/// Adjusts a name score by where the file lives and how recently it changed:
+/// shallow paths and fresh files float to the top.
+pub fn rank(score: i64, path: &Path, modified: Option<SystemTime>) -> i64 {
+    let depth = path.components().count() as i64;
+
+    let recency = modified
+        .and_then(|t| SystemTime::now().duration_since(t).ok())
+        .map(|age| match age {
+            a if a < DAY => 20,
+            a if a < DAY * 7 => 10,
+            a if a < DAY * 30 => 5,
+            _ => 0
+        })
+        .unwrap_or(0);
+
+    score - depth * PENALTY_DEPTH + recency
+}
+
+fn fold(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+fn is_boundary(hay: &[char], pos: usize) -> bool {
+    if pos == 0 {
+        return true;
+    }
+
+    let prev = hay[pos - 1];
+    let cur = hay[pos];
+
+    matches!(prev, '_' | '-' | '.' | ' ' | '/' | '\\')
+        || (prev.is_lowercase() && cur.is_uppercase())
+        || (!prev.is_ascii_digit() && cur.is_ascii_digit())
+}
+
+fn to_ranges(positions: &[usize]) -> Vec<(usize, usize)> {
+    let mut out: Vec<(usize, usize)> = Vec::new();
+
+    for &pos in positions {
+        match out.last_mut() {
+            Some((_, end)) if *end == pos => *end += 1,
+            _ => out.push((pos, pos + 1))
+        }
+    }
+
+    out
+}
@@ -1,72 +1,309 @@
-use std::collections::HashMap;
+use std::{collections::{HashMap, HashSet}, path::{Path, PathBuf}};
 
-use crate::models::{FileId, FileMeta};
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+
+use crate::{fs::watcher::FsChange, index::{fuzzy::{fuzzy_match, rank}, query::Query, trigram::TrigramIndex}, models::{FileId, FileKind, FileMeta, SearchHit}};
+
+type Ranked<'a> = (i64, &'a FileMeta, Vec<(usize, usize)>);
 
 #[derive(Default)]
 pub struct SimpleIndex {
-    pub files: HashMap<FileId, FileMeta>
+    files: HashMap<FileId, FileMeta>,
+    paths: HashMap<PathBuf, FileId>,
+    children: HashMap<PathBuf, HashSet<FileId>>,
+    names: TrigramIndex
 }
 
 impl SimpleIndex {
     pub fn new() -> Self {
-        Self { files: HashMap::new() }
+        Self::default()
     }
 
     pub fn insert(&mut self, meta: FileMeta) {
+        if let Some(old) = self.files.remove(&meta.id) {
+            self.unlink(&old);
+        }
+
+        self.link(&meta);
         self.files.insert(meta.id, meta);
     }
 
     pub fn remove(&mut self, id: FileId) {
-        self.files.remove(&id);
+        if let Some(old) = self.files.remove(&id) {
+            self.unlink(&old);
+        }
     }
 
     pub fn get(&self, id: FileId) -> Option<&FileMeta> {
         self.files.get(&id)
     }
 
+    /// Every entry, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = &FileMeta> {
+        self.files.values()
+    }
+
+    /// Records that `id` has a cached thumbnail now. Nothing else about an
+    /// entry may change in place, as the path lookups are keyed on it.
+    pub fn mark_thumbnailed(&mut self, id: FileId) {
+        if let Some(meta) = self.files.get_mut(&id) {
+            meta.has_thumbnail = true;
+        }
+    }
+
+    pub fn id_for_path(&self, path: &Path) -> Option<FileId> {
+        self.paths.get(&normalize(path)).copied()
+    }
+
+    pub fn contains_path(&self, path: &Path) -> bool {
+        self.paths.contains_key(&normalize(path))
+    }
+
+    pub fn children_of(&self, dir: &Path) -> impl Iterator<Item = &FileMeta> {
+        self.children
+            .get(&normalize(dir))
+            .into_iter()
+            .flatten()
+            .filter_map(|id| self.files.get(id))
+    }
+
+    /// Ids of `root` and everything below it, found through the children map.
+    pub fn subtree(&self, root: &Path) -> Vec<FileId> {
+        let root = normalize(root);
+        let mut out: Vec<FileId> = self.paths.get(&root).copied().into_iter().collect();
+        let mut stack = vec![root];
+
+        while let Some(dir) = stack.pop() {
+            let Some(ids) = self.children.get(&dir) else { continue };
+
+            for id in ids {
+                out.push(*id);
+                if let Some(meta) = self.files.get(id) {
+                    stack.push(normalize(&meta.path));
+                }
+            }
+        }
+
+        out
+    }
+
+    pub fn remove_subtree(&mut self, root: &Path) -> usize {
+        let ids = self.subtree(root);
+        for id in &ids {
+            self.remove(*id);
+        }
+
+        ids.len()
+    }
+
     pub fn len(&self) -> usize {
         self.files.len()
     }
-    
+
     pub fn apply_change(&mut self, id: FileId, meta: Option<FileMeta>) {
         match meta {
             Some(m) => {
-                self.files.insert(id, m);
+                self.insert(m);
             }
             None => {
-                self.files.remove(&id);
+                match self.files.get(&id) {
+                    Some(old) if matches!(old.kind, FileKind::Directory) => {
+                        let path = old.path.clone();
+                        self.remove_subtree(&path);
+                    }
+                    _ => self.remove(id)
+                }
             }
         }
     }
-    
+
+    pub fn apply(&mut self, change: &FsChange) {
+        match change {
+            FsChange::Created(id, meta) | FsChange::Modified(id, meta) => self.apply_change(*id, Some(meta.clone())),
+            FsChange::Deleted(id) => self.apply_change(*id, None),
+            FsChange::Renamed { id, from, to } => self.rename(*id, from, to)
+        }
+    }
+
+    /// Moves `id` from `from` to `to`, re-pathing everything below it when it
+    /// is a directory. Whatever was indexed at `to` before is replaced.
+    pub fn rename(&mut self, id: FileId, from: &Path, to: &Path) {
+        if let Some(existing) = self.id_for_path(to)
+            && existing != id
+        {
+            self.apply_change(existing, None);
+        }
+
+        let ids = match self.files.get(&id) {
+            Some(meta) if normalize(&meta.path) == normalize(from) => self.subtree(from),
+            Some(_) => vec![id],
+            None => return
+        };
+
+        for moved in ids {
+            let Some(mut meta) = self.files.get(&moved).cloned() else { continue };
+
+            meta.path = match meta.path.strip_prefix(from) {
+                Ok(rest) if !rest.as_os_str().is_empty() => to.join(rest),
+                _ => to.to_path_buf()
+            };
+
+            self.insert(meta);
+        }
+    }
+
     pub fn apply_full_scan(&mut self, new_files: Vec<FileMeta>) {
         self.files.clear();
+        self.paths.clear();
+        self.children.clear();
+        self.names.clear();
+
         for meta in new_files {
-            self.files.insert(meta.id, meta);
+            self.insert(meta);
         }
     }
 
+    /// Compares everything indexed below `root` with a fresh scan of it and
+    /// returns the changes that bring it up to date. Entries outside `root`
+    /// are never touched, and renamed entries keep their thumbnail state.
+    pub fn diff_scan(&self, root: &Path, scanned: Vec<FileMeta>) -> Vec<FsChange> {
+        let mut stale: HashSet<FileId> = self.subtree(root).into_iter().collect();
+        let mut changes = Vec::new();
+
+        for mut meta in scanned {
+            stale.remove(&meta.id);
+
+            let change = match self.files.get(&meta.id) {
+                None => FsChange::Created(meta.id, meta),
+                Some(old) if old.size == meta.size && old.modified == meta.modified && old.kind == meta.kind => {
+                    if old.path == meta.path {
+                        continue;
+                    }
+                    meta.has_thumbnail = old.has_thumbnail;
+                    FsChange::Modified(meta.id, meta)
+                }
+                Some(_) => FsChange::Modified(meta.id, meta)
+            };
+
+            changes.push(change);
+        }
+
+        // Deletions go first so a path that now belongs to a new id is free
+        // by the time that id is linked.
+        let mut out: Vec<FsChange> = stale.into_iter().map(FsChange::Deleted).collect();
+        out.append(&mut changes);
+        out
+    }
+
     pub fn search(&self, query: &str, limit: usize) -> Vec<FileMeta> {
         let q = query.to_lowercase();
-        let mut out = Vec::new();
 
-        let mut entries: Vec<_> = self.files.values().collect();
-        entries.sort_by_key(|m| m.path.clone());
+        let hits: Vec<&FileMeta> = self.names
+            .matches(&q)
+            .into_iter()
+            .filter_map(|id| self.files.get(&id))
+            .collect();
 
-        for meta in entries {
-            let name = match meta.path.file_name() {
-                Some(n) => n.to_string_lossy().to_lowercase(),
-                None => continue
-            };
+        first_by_path(hits, limit)
+    }
 
-            if name.contains(&q) {
-                out.push(meta.clone());
-                if out.len() >= limit {
-                    break;
-                }
+    pub fn search_query(&self, query: &Query, limit: usize) -> Vec<FileMeta> {
+        let narrowest = query.required_names()
+            .into_iter()
+            .max_by_key(|n| n.chars().count());
+
+        let hits: Vec<&FileMeta> = match narrowest {
+            Some(name) => self.names
+                .matches(name)
+                .into_iter()
+                .filter_map(|id| self.files.get(&id))
+                .filter(|m| query.matches(m))
+                .collect(),
+            None => self.files
+                .par_iter()
+                .map(|(_, m)| m)
+                .filter(|m| query.matches(m))
+                .collect()
+        };
+
+        first_by_path(hits, limit)
+    }
+
+    pub fn search_fuzzy(&self, query: &str, limit: usize) -> Vec<SearchHit> {
+        let mut hits: Vec<Ranked> = self.files
+            .par_iter()
+            .filter_map(|(_, meta)| {
+                let name = meta.path.file_name()?.to_string_lossy();
+                let m = fuzzy_match(query, &name)?;
+
+                Some((rank(m.score, &meta.path, meta.modified), meta, m.ranges))
+            })
+            .collect();
+
+        let by_rank = |a: &Ranked, b: &Ranked| {
+            b.0.cmp(&a.0).then_with(|| a.1.path.cmp(&b.1.path))
+        };
+
+        if hits.len() > limit && limit > 0 {
+            hits.select_nth_unstable_by(limit - 1, by_rank);
+        }
+
+        hits.truncate(limit);
+        hits.sort_unstable_by(by_rank);
+
+        hits.into_iter()
+            .map(|(score, meta, ranges)| SearchHit { meta: meta.clone(), score: Some(score), ranges })
+            .collect()
+    }
+
+    fn link(&mut self, meta: &FileMeta) {
+        let path = normalize(&meta.path);
+
+        if let Some(parent) = path.parent() {
+            self.children
+                .entry(parent.to_path_buf())
+                .or_default()
+                .insert(meta.id);
+        }
+
+        if let Some(name) = path.file_name() {
+            self.names.insert(meta.id, &name.to_string_lossy());
+        }
+
+        self.paths.insert(path, meta.id);
+    }
+
+    fn unlink(&mut self, meta: &FileMeta) {
+        let path = normalize(&meta.path);
+
+        if let Some(parent) = path.parent()
+            && let Some(siblings) = self.children.get_mut(parent)
+        {
+            siblings.remove(&meta.id);
+            if siblings.is_empty() {
+                self.children.remove(parent);
             }
         }
 
-        out
+        self.names.remove(meta.id);
+
+        if self.paths.get(&path) == Some(&meta.id) {
+            self.paths.remove(&path);
+        }
+    }
+}
+
+fn first_by_path(mut hits: Vec<&FileMeta>, limit: usize) -> Vec<FileMeta> {
+    if hits.len() > limit && limit > 0 {
+        hits.select_nth_unstable_by(limit - 1, |a, b| a.path.cmp(&b.path));
     }
-}
\ No newline at end of file
+
+    hits.truncate(limit);
+    hits.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+
+    hits.into_iter().cloned().collect()
+}
+
+pub fn normalize(p: &Path) -> PathBuf {
+    p.components().collect()
+}
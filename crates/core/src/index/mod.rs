@@ -1 +1,5 @@
-pub mod index;
\ No newline at end of file
+pub mod index;
+pub mod store;
+pub mod trigram;
+pub mod fuzzy;
+pub mod query;
\ No newline at end of file
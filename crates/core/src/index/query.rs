@@ -0,0 +1,473 @@
+use std::{path::Path, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use thiserror::Error;
+
+use crate::models::{FileKind, FileMeta};
+
+const DAY_SECS: u64 = 24 * 60 * 60;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum QueryError {
+    #[error("unterminated quote starting at column {0}")]
+    UnterminatedQuote(usize),
+
+    #[error("unexpected `{0}` at column {1}")]
+    Unexpected(String, usize),
+
+    #[error("missing closing parenthesis for the one at column {0}")]
+    UnclosedGroup(usize),
+
+    #[error("expected a term after `{0}`")]
+    DanglingOperator(String),
+
+    #[error("unknown filter `{0}:`; expected ext, size, modified, kind or path")]
+    UnknownFilter(String),
+
+    #[error("invalid value `{value}` for `{filter}:`: {reason}")]
+    InvalidValue { filter: &'static str, value: String, reason: &'static str }
+}
+
+pub type QueryResult<T> = Result<T, QueryError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cmp {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq
+}
+
+impl Cmp {
+    fn test<T: PartialOrd>(self, lhs: T, rhs: T) -> bool {
+        match self {
+            Cmp::Lt => lhs < rhs,
+            Cmp::Le => lhs <= rhs,
+            Cmp::Gt => lhs > rhs,
+            Cmp::Ge => lhs >= rhs,
+            Cmp::Eq => lhs == rhs
+        }
+    }
+
+    fn flip(self) -> Self {
+        match self {
+            Cmp::Lt => Cmp::Gt,
+            Cmp::Le => Cmp::Ge,
+            Cmp::Gt => Cmp::Lt,
+            Cmp::Ge => Cmp::Le,
+            Cmp::Eq => Cmp::Eq
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KindFilter {
+    File,
+    Directory,
+    Symlink,
+    Other
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Predicate {
+    Name(String),
+    Ext(String),
+    Size(Cmp, u64),
+    /// `modified:<7d` is relative to now, `modified:<2024-01-31` is absolute.
+    Modified(Cmp, SystemTime),
+    Kind(KindFilter),
+    Path(String)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    All,
+    Term(Predicate),
+    Not(Box<Query>),
+    And(Vec<Query>),
+    Or(Vec<Query>)
+}
+
+impl Query {
+    /// Parses queries such as `ext:pdf size:>10MB modified:<7d path:invoices`.
+    ///
+    /// Terms are ANDed by juxtaposition; `OR`, `AND`, `NOT`/`-` and
+    /// parentheses are supported, with NOT binding tighter than AND and AND
+    /// tighter than OR. Quoted phrases match literally.
+    pub fn parse(input: &str) -> QueryResult<Query> {
+        let tokens = tokenize(input)?;
+        let mut parser = Parser { tokens, pos: 0, now: SystemTime::now() };
+
+        if parser.tokens.is_empty() {
+            return Ok(Query::All);
+        }
+
+        let query = parser.parse_or()?;
+
+        if let Some(tok) = parser.peek() {
+            return Err(QueryError::Unexpected(tok.text.clone(), tok.col));
+        }
+
+        Ok(query)
+    }
+
+    pub fn matches(&self, meta: &FileMeta) -> bool {
+        match self {
+            Query::All => true,
+            Query::Term(p) => p.matches(meta),
+            Query::Not(q) => !q.matches(meta),
+            Query::And(qs) => qs.iter().all(|q| q.matches(meta)),
+            Query::Or(qs) => qs.iter().any(|q| q.matches(meta))
+        }
+    }
+
+    /// Lowercased name fragments every match must contain, usable to narrow
+    /// candidates through the trigram index before evaluating the query.
+    pub fn required_names(&self) -> Vec<&str> {
+        match self {
+            Query::Term(Predicate::Name(n)) => vec![n.as_str()],
+            Query::And(qs) => qs.iter().flat_map(|q| q.required_names()).collect(),
+            _ => Vec::new()
+        }
+    }
+}
+
+impl Predicate {
+    fn matches(&self, meta: &FileMeta) -> bool {
+        match self {
+            Predicate::Name(needle) => file_name(&meta.path).contains(needle.as_str()),
+            Predicate::Ext(ext) => meta.path
+                .extension()
+                .map(|e| e.to_string_lossy().to_lowercase() == *ext)
+                .unwrap_or(false),
+            Predicate::Size(cmp, bytes) => {
+                !matches!(meta.kind, FileKind::Directory) && cmp.test(meta.size, *bytes)
+            }
+            Predicate::Modified(cmp, at) => meta.modified
+                .map(|m| cmp.test(m, *at))
+                .unwrap_or(false),
+            Predicate::Kind(KindFilter::Directory) => matches!(meta.kind, FileKind::Directory),
+            Predicate::Kind(KindFilter::File) => matches!(meta.kind, FileKind::File),
+            Predicate::Kind(KindFilter::Symlink) => matches!(meta.kind, FileKind::Symlink { .. }),
+            Predicate::Kind(KindFilter::Other) => matches!(meta.kind, FileKind::Other),
+            Predicate::Path(needle) => meta.path
+                .to_string_lossy()
+                .to_lowercase()
+                .contains(needle.as_str())
+        }
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Word,
+    Phrase,
+    LParen,
+    RParen,
+    And,
+    Or,
+    Not
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    text: String,
+    col: usize
+}
+
+fn tokenize(input: &str) -> QueryResult<Vec<Token>> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut out = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let col = i + 1;
+
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        match c {
+            '(' => {
+                out.push(Token { kind: TokenKind::LParen, text: "(".into(), col });
+                i += 1;
+            }
+            ')' => {
+                out.push(Token { kind: TokenKind::RParen, text: ")".into(), col });
+                i += 1;
+            }
+            '-' => {
+                out.push(Token { kind: TokenKind::Not, text: "-".into(), col });
+                i += 1;
+            }
+            '"' => {
+                let end = chars[i + 1..]
+                    .iter()
+                    .position(|c| *c == '"')
+                    .ok_or(QueryError::UnterminatedQuote(col))?;
+
+                let text: String = chars[i + 1..i + 1 + end].iter().collect();
+                out.push(Token { kind: TokenKind::Phrase, text, col });
+                i += end + 2;
+            }
+            _ => {
+                let mut text = String::new();
+
+                while i < chars.len() && !chars[i].is_whitespace() && !matches!(chars[i], '(' | ')') {
+                    if chars[i] == '"' {
+                        let end = chars[i + 1..]
+                            .iter()
+                            .position(|c| *c == '"')
+                            .ok_or(QueryError::UnterminatedQuote(i + 1))?;
+
+                        text.extend(&chars[i + 1..i + 1 + end]);
+                        i += end + 2;
+                    } else {
+                        text.push(chars[i]);
+                        i += 1;
+                    }
+                }
+
+                let kind = match text.as_str() {
+                    "AND" => TokenKind::And,
+                    "OR" => TokenKind::Or,
+                    "NOT" => TokenKind::Not,
+                    _ => TokenKind::Word
+                };
+
+                out.push(Token { kind, text, col });
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    now: SystemTime
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let tok = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        tok
+    }
+
+    fn parse_or(&mut self) -> QueryResult<Query> {
+        let mut terms = vec![self.parse_and()?];
+
+        while self.peek().is_some_and(|t| t.kind == TokenKind::Or) {
+            let op = self.next().expect("peeked");
+            if self.peek().is_none() {
+                return Err(QueryError::DanglingOperator(op.text));
+            }
+            terms.push(self.parse_and()?);
+        }
+
+        Ok(if terms.len() == 1 { terms.remove(0) } else { Query::Or(terms) })
+    }
+
+    fn parse_and(&mut self) -> QueryResult<Query> {
+        let mut terms = vec![self.parse_not()?];
+
+        loop {
+            match self.peek().map(|t| &t.kind) {
+                Some(TokenKind::And) => {
+                    let op = self.next().expect("peeked");
+                    if self.peek().is_none() {
+                        return Err(QueryError::DanglingOperator(op.text));
+                    }
+                }
+                Some(TokenKind::Or | TokenKind::RParen) | None => break,
+                Some(_) => {}
+            }
+
+            terms.push(self.parse_not()?);
+        }
+
+        Ok(if terms.len() == 1 { terms.remove(0) } else { Query::And(terms) })
+    }
+
+    fn parse_not(&mut self) -> QueryResult<Query> {
+        if self.peek().is_some_and(|t| t.kind == TokenKind::Not) {
+            let op = self.next().expect("peeked");
+            if self.peek().is_none() {
+                return Err(QueryError::DanglingOperator(op.text));
+            }
+            return Ok(Query::Not(Box::new(self.parse_not()?)));
+        }
+
+        self.parse_atom()
+    }
+
+    fn parse_atom(&mut self) -> QueryResult<Query> {
+        let Some(tok) = self.next() else {
+            let last = self.tokens.last().map(|t| t.text.clone()).unwrap_or_default();
+            return Err(QueryError::DanglingOperator(last));
+        };
+
+        match tok.kind {
+            TokenKind::LParen => {
+                let inner = self.parse_or()?;
+                match self.next() {
+                    Some(t) if t.kind == TokenKind::RParen => Ok(inner),
+                    _ => Err(QueryError::UnclosedGroup(tok.col))
+                }
+            }
+            TokenKind::Phrase => Ok(Query::Term(Predicate::Name(tok.text.to_lowercase()))),
+            TokenKind::Word => self.parse_word(&tok.text),
+            _ => Err(QueryError::Unexpected(tok.text, tok.col))
+        }
+    }
+
+    fn parse_word(&self, word: &str) -> QueryResult<Query> {
+        let Some((filter, value)) = word.split_once(':') else {
+            return Ok(Query::Term(Predicate::Name(word.to_lowercase())));
+        };
+
+        let filter = filter.to_lowercase();
+        let pred = match filter.as_str() {
+            "ext" => Predicate::Ext(value.trim_start_matches('.').to_lowercase()),
+            "path" => Predicate::Path(value.to_lowercase()),
+            "kind" | "type" => Predicate::Kind(parse_kind(value)?),
+            "size" => {
+                let (cmp, rest) = split_cmp(value);
+                Predicate::Size(cmp, parse_size(rest)?)
+            }
+            "modified" | "mtime" => {
+                let (cmp, rest) = split_cmp(value);
+                return self.parse_modified(cmp, rest);
+            }
+            _ => return Err(QueryError::UnknownFilter(filter))
+        };
+
+        Ok(Query::Term(pred))
+    }
+
+    fn parse_modified(&self, cmp: Cmp, value: &str) -> QueryResult<Query> {
+        if let Some(day) = parse_date(value) {
+            if cmp == Cmp::Eq {
+                let next = day + Duration::from_secs(DAY_SECS);
+                return Ok(Query::And(vec![
+                    Query::Term(Predicate::Modified(Cmp::Ge, day)),
+                    Query::Term(Predicate::Modified(Cmp::Lt, next))
+                ]));
+            }
+
+            return Ok(Query::Term(Predicate::Modified(cmp, day)));
+        }
+
+        let age = parse_age(value)?;
+        let at = self.now.checked_sub(age).unwrap_or(UNIX_EPOCH);
+
+        // `<7d` reads as "less than seven days old", i.e. modified after the
+        // cutoff; a bare `7d` means the same.
+        let cmp = if cmp == Cmp::Eq { Cmp::Lt } else { cmp };
+        Ok(Query::Term(Predicate::Modified(cmp.flip(), at)))
+    }
+}
+
+fn split_cmp(value: &str) -> (Cmp, &str) {
+    for (prefix, cmp) in [("<=", Cmp::Le), (">=", Cmp::Ge), ("<", Cmp::Lt), (">", Cmp::Gt), ("=", Cmp::Eq)] {
+        if let Some(rest) = value.strip_prefix(prefix) {
+            return (cmp, rest);
+        }
+    }
+
+    (Cmp::Eq, value)
+}
+
+fn parse_kind(value: &str) -> QueryResult<KindFilter> {
+    match value.to_lowercase().as_str() {
+        "file" | "f" => Ok(KindFilter::File),
+        "dir" | "directory" | "folder" | "d" => Ok(KindFilter::Directory),
+        "link" | "symlink" | "l" => Ok(KindFilter::Symlink),
+        "other" | "special" => Ok(KindFilter::Other),
+        _ => Err(QueryError::InvalidValue { filter: "kind", value: value.into(), reason: "expected file, dir, link or other" })
+    }
+}
+
+fn parse_size(value: &str) -> QueryResult<u64> {
+    let invalid = |reason| QueryError::InvalidValue { filter: "size", value: value.into(), reason };
+
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (num, unit) = value.split_at(split);
+
+    let num: f64 = num.parse().map_err(|_| invalid("expected a number such as 10MB"))?;
+
+    let mult: u64 = match unit.to_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" => 1 << 10,
+        "m" | "mb" => 1 << 20,
+        "g" | "gb" => 1 << 30,
+        "t" | "tb" => 1 << 40,
+        _ => return Err(invalid("unknown unit; expected B, KB, MB, GB or TB"))
+    };
+
+    Ok((num * mult as f64) as u64)
+}
+
+fn parse_age(value: &str) -> QueryResult<Duration> {
+    let invalid = |reason| QueryError::InvalidValue { filter: "modified", value: value.into(), reason };
+
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| invalid("missing unit; expected s, m, h, d, w or y"))?;
+    let (num, unit) = value.split_at(split);
+
+    let num: u64 = num.parse().map_err(|_| invalid("expected an age such as 7d or a date such as 2024-01-31"))?;
+
+    let secs = match unit {
+        "s" => 1,
+        "m" | "min" => 60,
+        "h" => 60 * 60,
+        "d" => DAY_SECS,
+        "w" => 7 * DAY_SECS,
+        "mo" => 30 * DAY_SECS,
+        "y" => 365 * DAY_SECS,
+        _ => return Err(invalid("unknown unit; expected s, m, h, d, w, mo or y"))
+    };
+
+    Ok(Duration::from_secs(num.saturating_mul(secs)))
+}
+
+/// Parses `YYYY-MM-DD` as midnight UTC.
+fn parse_date(value: &str) -> Option<SystemTime> {
+    let mut parts = value.splitn(3, '-');
+    let y: i64 = parts.next()?.parse().ok()?;
+    let m: i64 = parts.next()?.parse().ok()?;
+    let d: i64 = parts.next()?.parse().ok()?;
+
+    if !(1..=12).contains(&m) || !(1..=31).contains(&d) {
+        return None;
+    }
+
+    // Howard Hinnant's days-from-civil.
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+
+    let secs = u64::try_from(days).ok()? * DAY_SECS;
+    Some(UNIX_EPOCH + Duration::from_secs(secs))
+}
@@ -0,0 +1,73 @@
+use std::{fs::{self, File}, io::{BufReader, BufWriter, Write}, path::{Path, PathBuf}};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{METADTA_VERSION, index::index::SimpleIndex, models::FileMeta};
+
+#[derive(Serialize)]
+struct StoredIndexRef<'a> {
+    version: u8,
+    files: Vec<&'a FileMeta>
+}
+
+#[derive(Deserialize)]
+struct StoredIndex {
+    version: u8,
+    files: Vec<FileMeta>
+}
+
+pub struct IndexStore {
+    path: PathBuf
+}
+
+impl IndexStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns `None` when nothing was persisted yet or the snapshot was
+    /// written with a different `METADTA_VERSION`.
+    pub fn load(&self) -> anyhow::Result<Option<SimpleIndex>> {
+        let file = match File::open(&self.path) {
+            Ok(f) => f,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into())
+        };
+
+        let stored: StoredIndex = bincode::deserialize_from(BufReader::new(file))?;
+
+        if stored.version != METADTA_VERSION {
+            return Ok(None);
+        }
+
+        let mut index = SimpleIndex::new();
+        index.apply_full_scan(stored.files);
+
+        Ok(Some(index))
+    }
+
+    pub fn save(&self, index: &SimpleIndex) -> anyhow::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let stored = StoredIndexRef {
+            version: METADTA_VERSION,
+            files: index.iter().collect()
+        };
+
+        let tmp = self.path.with_extension("tmp");
+        let mut writer = BufWriter::new(File::create(&tmp)?);
+        bincode::serialize_into(&mut writer, &stored)?;
+        writer.flush()?;
+        drop(writer);
+
+        fs::rename(tmp, &self.path)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,108 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::models::FileId;
+
+type Trigram = [char; 3];
+
+/// Substring index over lowercased file names.
+///
+/// Every name is split into overlapping trigrams, each pointing at the set of
+/// files containing it. Queries intersect the posting lists of their own
+/// trigrams and only verify the surviving candidates.
+#[derive(Default)]
+pub struct TrigramIndex {
+    postings: HashMap<Trigram, HashSet<FileId>>,
+    names: HashMap<FileId, String>
+}
+
+impl TrigramIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, id: FileId, name: &str) {
+        self.remove(id);
+
+        let name = name.to_lowercase();
+        for gram in trigrams(&name) {
+            self.postings.entry(gram).or_default().insert(id);
+        }
+
+        self.names.insert(id, name);
+    }
+
+    pub fn remove(&mut self, id: FileId) {
+        let Some(name) = self.names.remove(&id) else { return };
+
+        for gram in trigrams(&name) {
+            if let Some(ids) = self.postings.get_mut(&gram) {
+                ids.remove(&id);
+                if ids.is_empty() {
+                    self.postings.remove(&gram);
+                }
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.postings.clear();
+        self.names.clear();
+    }
+
+    /// Ids whose lowercased name contains `query`, which must already be
+    /// lowercased.
+    pub fn matches(&self, query: &str) -> Vec<FileId> {
+        if query.is_empty() {
+            return self.names.keys().copied().collect();
+        }
+
+        let grams = trigrams(query);
+
+        // Too short to have a trigram, and short enough to sit in most
+        // posting keys, so checking every name directly is cheaper.
+        if grams.is_empty() {
+            return self.names
+                .iter()
+                .filter(|(_, name)| name.contains(query))
+                .map(|(id, _)| *id)
+                .collect();
+        }
+
+        let mut lists = Vec::with_capacity(grams.len());
+        for gram in &grams {
+            match self.postings.get(gram) {
+                Some(ids) => lists.push(ids),
+                None => return Vec::new()
+            }
+        }
+
+        lists.sort_by_key(|ids| ids.len());
+        let (smallest, rest) = lists.split_first().expect("query has at least one trigram");
+
+        smallest
+            .iter()
+            .filter(|id| rest.iter().all(|ids| ids.contains(id)))
+            // Only a three-character query is fully described by its one
+            // trigram; "aaaa" dedups to the same gram as "aaa".
+            .filter(|id| query.chars().count() == 3 || self.name_contains(**id, query))
+            .copied()
+            .collect()
+    }
+
+    fn name_contains(&self, id: FileId, query: &str) -> bool {
+        self.names.get(&id).is_some_and(|n| n.contains(query))
+    }
+}
+
+fn trigrams(s: &str) -> Vec<Trigram> {
+    let chars: Vec<char> = s.chars().collect();
+
+    let mut out: Vec<Trigram> = chars
+        .windows(3)
+        .map(|w| [w[0], w[1], w[2]])
+        .collect();
+
+    out.sort_unstable();
+    out.dedup();
+    out
+}
@@ -6,4 +6,4 @@ pub mod thumbnails;
 
 pub use engine::runtime::EngineRuntime;
 
-const METADTA_VERSION: u8 = 1;
+const METADTA_VERSION: u8 = 2;
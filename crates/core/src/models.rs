@@ -5,10 +5,13 @@ use serde::{Deserialize, Serialize};
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct FileId(pub u128);
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum FileKind {
     File,
-    Directory
+    Directory,
+    Symlink { target: PathBuf, broken: bool },
+    /// Sockets, FIFOs and device nodes.
+    Other
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -21,4 +24,30 @@ pub struct FileMeta {
     pub modified: Option<SystemTime>,
     pub created: Option<SystemTime>,
     pub has_thumbnail: bool
-}
\ No newline at end of file
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchMode {
+    #[default]
+    Substring,
+    Fuzzy,
+    Query
+}
+
+/// What is known about a set of thumbnails right now. `pending` ones are
+/// queued or being generated; `missing` ones are neither, either because the
+/// file is not indexed or because no thumbnail could be made for it.
+#[derive(Clone, Debug, Default)]
+pub struct ThumbnailBatch {
+    pub ready: Vec<(FileId, Vec<u8>)>,
+    pub pending: Vec<FileId>,
+    pub missing: Vec<FileId>
+}
+
+#[derive(Clone, Debug)]
+pub struct SearchHit {
+    pub meta: FileMeta,
+    pub score: Option<i64>,
+    pub ranges: Vec<(usize, usize)>
+}
@@ -54,4 +54,9 @@ impl ThumbnailCache {
 
         Ok(())
     }
+
+    pub fn invalidate(&self, id: FileId) {
+        self.mem.remove(&id);
+        let _ = fs::remove_file(self.cfg.disk_path_for(id));
+    }
 }
\ No newline at end of file
@@ -65,7 +65,8 @@ pub fn generate_thumbnail(
     meta: &FileMeta,
     cfg: &ThumbnailConfig
 ) -> ThumbnailResult<Vec<u8>> {
-    if matches!(meta.kind, FileKind::Directory) {
+    // Reading a FIFO or device node would block the worker.
+    if !matches!(meta.kind, FileKind::File | FileKind::Symlink { broken: false, .. }) {
         return Err(ThumbnailError::Unsupported);
     }
 
@@ -1,12 +1,70 @@
-use std::{collections::HashSet, sync::{Arc, atomic::{AtomicBool, Ordering}, mpsc::{Receiver, SyncSender, sync_channel}}, thread::{self, JoinHandle}};
+use std::{collections::{HashMap, HashSet, VecDeque}, sync::{Arc, atomic::{AtomicBool, Ordering}}, thread::{self, JoinHandle}};
 
-use parking_lot::RwLock;
+use parking_lot::{Condvar, Mutex, RwLock};
 
-use crate::{index::index::SimpleIndex, models::{FileId, FileMeta}, thumbnails::{cache::ThumbnailCache, generator::generate_thumbnail}};
+use crate::{engine::events::{EngineEvent, EventBus}, index::index::SimpleIndex, models::{FileId, FileMeta}, thumbnails::{cache::ThumbnailCache, generator::generate_thumbnail}};
+
+/// How many `Low` requests may wait at once. Past this the oldest are
+/// dropped; asking for one of them again queues it anew.
+pub const LOW_QUEUE: usize = 64;
+
+/// Which requests the worker gets to first. Files seen by the watcher are
+/// queued as `Low` so that whatever is on screen comes first.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ThumbnailPriority {
+    Low,
+    #[default]
+    Normal,
+    High
+}
+
+/// Requests waiting for the worker, one queue per priority. `queued` has
+/// the lane each waiting id will be taken from, and `pending` everything
+/// queued or being generated.
+#[derive(Debug, Default)]
+struct Queue {
+    lanes: [VecDeque<FileMeta>; 3],
+    queued: HashMap<FileId, ThumbnailPriority>,
+    pending: HashSet<FileId>,
+    closed: bool
+}
+
+impl Queue {
+    /// The next request, skipping copies left behind in a lower lane when
+    /// the id was queued again at a higher priority.
+    fn pop(&mut self) -> Option<FileMeta> {
+        let Queue { lanes, queued, .. } = self;
+
+        lanes.iter_mut().enumerate().rev().find_map(|(lane, requests)| {
+            while let Some(meta) = requests.pop_front() {
+                if queued.get(&meta.id).is_some_and(|&p| p as usize == lane) {
+                    queued.remove(&meta.id);
+                    return Some(meta);
+                }
+            }
+            None
+        })
+    }
+
+    /// Drops the oldest `Low` requests until the lane fits in `LOW_QUEUE`.
+    fn trim_low(&mut self) {
+        let Queue { lanes, queued, pending, .. } = self;
+        let low = &mut lanes[ThumbnailPriority::Low as usize];
+
+        while low.len() > LOW_QUEUE {
+            let Some(meta) = low.pop_front() else { break };
+
+            if queued.get(&meta.id) == Some(&ThumbnailPriority::Low) {
+                queued.remove(&meta.id);
+                pending.remove(&meta.id);
+            }
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct ThumbnailWorker {
-    tx: SyncSender<Option<FileMeta>>,
+    queue: Arc<(Mutex<Queue>, Condvar)>,
     stop: Arc<AtomicBool>,
     _handle: Arc<JoinHandle<()>>
 }
@@ -14,73 +72,102 @@ pub struct ThumbnailWorker {
 impl ThumbnailWorker {
     pub fn new(
         cache: Arc<ThumbnailCache>,
-        index: Arc<RwLock<SimpleIndex>>
+        index: Arc<RwLock<SimpleIndex>>,
+        events: Arc<EventBus>
     ) -> Self {
-        let (tx, rx) = sync_channel::<Option<FileMeta>>(64);
+        let queue = Arc::new((Mutex::new(Queue::default()), Condvar::new()));
         let stop_flag = Arc::new(AtomicBool::new(false));
 
         let handle = {
+            let queue = queue.clone();
             let stop = stop_flag.clone();
             let cache = cache.clone();
             let index = index.clone();
 
             thread::spawn(move || {
                 println!("[thumb-worker] started");
-                worker_loop(rx, cache, index, stop)
+                worker_loop(queue, cache, index, events, stop)
             })
         };
 
-        Self { tx, stop: stop_flag, _handle: Arc::new(handle) }
+        Self { queue, stop: stop_flag, _handle: Arc::new(handle) }
     }
 
     pub fn submit(&self, meta: FileMeta) {
-        let _ = self.tx.send(Some(meta));
+        self.submit_with(meta, ThumbnailPriority::Normal);
+    }
+
+    /// Queues `meta` unless it is already pending. A request that is waiting
+    /// at a lower priority moves up to `priority`.
+    pub fn submit_with(&self, meta: FileMeta, priority: ThumbnailPriority) {
+        let (lock, ready) = &*self.queue;
+        let mut queue = lock.lock();
+
+        let waiting_below = queue.queued.get(&meta.id).is_some_and(|&p| p < priority);
+
+        if queue.pending.insert(meta.id) || waiting_below {
+            queue.queued.insert(meta.id, priority);
+            queue.lanes[priority as usize].push_back(meta);
+            queue.trim_low();
+            ready.notify_one();
+        }
+    }
+
+    /// Whether `id` is queued or being generated right now.
+    pub fn is_pending(&self, id: FileId) -> bool {
+        self.queue.0.lock().pending.contains(&id)
     }
 
     pub fn shutdown(&self) {
         self.stop.store(true, Ordering::Relaxed);
-        let _ = self.tx.send(None);
+
+        let (lock, ready) = &*self.queue;
+        lock.lock().closed = true;
+        ready.notify_all();
     }
 }
 
 fn worker_loop(
-    rx: Receiver<Option<FileMeta>>,
+    queue: Arc<(Mutex<Queue>, Condvar)>,
     cache: Arc<ThumbnailCache>,
     index: Arc<RwLock<SimpleIndex>>,
+    events: Arc<EventBus>,
     stop: Arc<AtomicBool>
 ) {
-    let mut inflight: HashSet<FileId> = HashSet::new();
+    let (lock, ready) = &*queue;
 
     while !stop.load(Ordering::Relaxed) {
-        let Some(meta) = rx.recv().ok().flatten() else { break };
+        let meta = {
+            let mut queue = lock.lock();
+            loop {
+                if queue.closed {
+                    return;
+                }
+                if let Some(meta) = queue.pop() {
+                    break meta;
+                }
+                ready.wait(&mut queue);
+            }
+        };
 
         let id = meta.id;
-        
-        if cache.get(id).is_some() {
-            continue;
-        }
-
-        if inflight.contains(&id) {
-            continue;
-        }
 
-        inflight.insert(id);
+        if cache.get(id).is_none() {
+            match generate_thumbnail(&meta, &cache.cfg) {
+                Ok(bytes) => {
+                    println!("[thumb-worker] generated {:?}", meta.path);
+                    let _ = cache.store(id, &bytes);
 
-        match generate_thumbnail(&meta, &cache.cfg) {
-            Ok(bytes) => {
-                println!("[thumb-worker] generated {:?}", meta.path);
-                let _ = cache.store(id, &bytes);
+                    index.write().mark_thumbnailed(id);
 
-                // ✅ Update index: thumbnail now exists
-                if let Some(m) = index.write().files.get_mut(&id) {
-                    m.has_thumbnail = true;
+                    events.publish(&EngineEvent::ThumbnailReady { id, path: meta.path.clone() });
+                }
+                Err(e) => {
+                    println!("[thumb-worker] FAILED {:?} -> {:?}", meta.path, e);
                 }
-            }
-            Err(e) => {
-                println!("[thumb-worker] FAILED {:?} -> {:?}", meta.path, e);
             }
         }
 
-        inflight.remove(&id);
+        lock.lock().pending.remove(&id);
     }
-}
\ No newline at end of file
+}
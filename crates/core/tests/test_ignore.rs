@@ -0,0 +1,35 @@
+use std::fs;
+
+use lunio_core::EngineRuntime;
+
+#[test]
+fn ignore_rules_are_honored() {
+    let root = std::env::temp_dir().join(format!("lunio-ignore-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("repo/.git")).unwrap();
+    fs::create_dir_all(root.join("repo/target/debug")).unwrap();
+    fs::create_dir_all(root.join("repo/src/gen")).unwrap();
+    fs::write(root.join("repo/.git/HEAD"), b"ref").unwrap();
+    fs::write(root.join("repo/.gitignore"), b"target/\n*.log\n").unwrap();
+    fs::write(root.join("repo/.lunioignore"), b"secret.txt\n").unwrap();
+    fs::write(root.join("repo/src/gen/.gitignore"), b"*\n!keep.rs\n").unwrap();
+    fs::write(root.join("repo/target/debug/app"), b"").unwrap();
+    fs::write(root.join("repo/build.log"), b"").unwrap();
+    fs::write(root.join("repo/secret.txt"), b"").unwrap();
+    fs::write(root.join("repo/src/main.rs"), b"").unwrap();
+    fs::write(root.join("repo/src/gen/out.rs"), b"").unwrap();
+    fs::write(root.join("repo/src/gen/keep.rs"), b"").unwrap();
+
+    let engine = EngineRuntime::new(root.join(".cache"), None, None);
+    engine.full_scan(root.join("repo"));
+
+    assert_eq!(engine.search("main.rs", 10).len(), 1);
+    assert_eq!(engine.search("keep.rs", 10).len(), 1);
+    assert!(engine.search("out.rs", 10).is_empty());
+    assert!(engine.search("app", 10).is_empty());
+    assert!(engine.search("build.log", 10).is_empty());
+    assert!(engine.search("secret", 10).is_empty());
+    assert!(engine.search("HEAD", 10).is_empty());
+
+    let _ = fs::remove_dir_all(&root);
+}
@@ -0,0 +1,54 @@
+#![cfg(unix)]
+
+use std::{fs, os::unix::fs::symlink};
+
+use lunio_core::{fs::{filter::PathFilter, scan::{ScanOptions, scan_root_with}}, models::FileKind};
+
+#[test]
+fn symlinks_and_hidden_files_follow_policy() {
+    let root = std::env::temp_dir().join(format!("lunio-links-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("tree/real/.config")).unwrap();
+    fs::write(root.join("tree/real/data.txt"), b"").unwrap();
+    fs::write(root.join("tree/real/.config/settings"), b"").unwrap();
+    symlink("real/data.txt", root.join("tree/alias.txt")).unwrap();
+    symlink("missing.txt", root.join("tree/dangling")).unwrap();
+    symlink("..", root.join("tree/real/up")).unwrap();
+    symlink("real", root.join("tree/again")).unwrap();
+
+    let tree = root.join("tree");
+    let names = |opts: &ScanOptions| -> Vec<String> {
+        let mut out: Vec<_> = scan_root_with(&tree, opts)
+            .iter()
+            .map(|m| m.path.strip_prefix(&tree).unwrap().to_string_lossy().into_owned())
+            .collect();
+        out.sort();
+        out
+    };
+
+    let metas = scan_root_with(&tree, &ScanOptions::default());
+    let kind_of = |name: &str| metas.iter().find(|m| m.path.ends_with(name)).map(|m| m.kind.clone());
+
+    assert_eq!(kind_of("alias.txt"), Some(FileKind::Symlink { target: "real/data.txt".into(), broken: false }));
+    assert_eq!(kind_of("dangling"), Some(FileKind::Symlink { target: "missing.txt".into(), broken: true }));
+    assert!(names(&ScanOptions::default()).contains(&"real/.config/settings".to_string()));
+
+    let hidden = ScanOptions {
+        filter: Some(PathFilter::new(&tree, &[], false, false).unwrap()),
+        ..Default::default()
+    };
+    assert!(!names(&hidden).iter().any(|n| n.contains(".config")));
+
+    // `up` points back at the tree and `again` duplicates `real`, so only
+    // one of the two copies may be walked into.
+    let followed = names(&ScanOptions { follow_links: true, ..Default::default() });
+    assert!(!followed.iter().any(|n| n.starts_with("real/up/")));
+    assert!(!followed.iter().any(|n| n.starts_with("again/up/")));
+    assert_eq!(followed.iter().filter(|n| n.ends_with("data.txt")).count(), 1);
+    // The real path wins whichever readdir lists first; the link is still
+    // indexed itself.
+    assert!(followed.contains(&"real/data.txt".to_string()));
+    assert!(followed.contains(&"again".to_string()));
+
+    let _ = fs::remove_dir_all(&root);
+}
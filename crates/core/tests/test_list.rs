@@ -1,6 +1,6 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-use lunio_core::EngineRuntime;
+use lunio_core::{EngineRuntime, engine::listing::{ListOptions, NameOrder, SortDirection, SortKey}, models::{FileId, FileKind, FileMeta}};
 
 #[test]
 fn listing_works() {
@@ -10,4 +10,68 @@ fn listing_works() {
     
     println!("{:?}", results);
     assert!(results.len() > 0);
-}
\ No newline at end of file
+}
+
+#[test]
+fn listing_only_returns_direct_children() {
+    let root = std::env::temp_dir().join(format!("lunio-list-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(root.join("a/b")).unwrap();
+    std::fs::write(root.join("a/one.txt"), b"1").unwrap();
+    std::fs::write(root.join("a/b/two.txt"), b"2").unwrap();
+
+    let engine = EngineRuntime::new(root.join(".cache"), None, None);
+    engine.full_scan(&root);
+
+    let results = engine.list_dir(&root.join("a"));
+    let names: Vec<_> = results.iter()
+        .map(|m| m.path.file_name().unwrap().to_string_lossy().into_owned())
+        .collect();
+
+    assert_eq!(names, vec!["b", "one.txt"]);
+
+    let _ = std::fs::remove_dir_all(&root);
+}
+
+fn entry(path: &str, kind: FileKind, size: u64) -> FileMeta {
+    FileMeta {
+        version: 0,
+        id: FileId(size as u128),
+        path: PathBuf::from(path),
+        size,
+        kind,
+        modified: None,
+        created: None,
+        has_thumbnail: false
+    }
+}
+
+#[test]
+fn listings_sort_and_filter() {
+    let entries = vec![
+        entry("/d/file10.txt", FileKind::File, 1),
+        entry("/d/File2.txt", FileKind::File, 30),
+        entry("/d/music", FileKind::Directory, 0),
+        entry("/d/.hidden", FileKind::File, 5),
+        entry("/d/cover.PNG", FileKind::File, 20),
+        entry("/d/Albums", FileKind::Directory, 0)
+    ];
+    let names = |options: &ListOptions| -> Vec<String> {
+        options.apply(entries.clone())
+            .iter()
+            .map(|m| m.path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect()
+    };
+
+    // The old order: folders first, then byte-wise.
+    assert_eq!(names(&ListOptions::default()), ["Albums", "music", ".hidden", "File2.txt", "cover.PNG", "file10.txt"]);
+
+    let natural = ListOptions { name_order: NameOrder::Natural, include_hidden: false, ..Default::default() };
+    assert_eq!(names(&natural), ["Albums", "music", "cover.PNG", "File2.txt", "file10.txt"]);
+
+    let by_size = ListOptions { sort: SortKey::Size, direction: SortDirection::Descending, folders_first: false, ..Default::default() };
+    assert_eq!(names(&by_size)[..3], ["File2.txt", "cover.PNG", ".hidden"]);
+
+    let images = ListOptions { extensions: vec![".png".into()], ..Default::default() };
+    assert_eq!(names(&images), ["Albums", "music", "cover.PNG"]);
+}
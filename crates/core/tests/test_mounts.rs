@@ -0,0 +1,25 @@
+use std::path::Path;
+
+use lunio_core::fs::mounts::parse_mountinfo;
+
+const MOUNTINFO: &str = "\
+23 28 0:22 / /proc rw,relatime - proc proc rw
+28 1 259:2 / / rw,relatime shared:1 - ext4 /dev/nvme0n1p2 rw
+40 28 0:35 / /mnt/nas rw,relatime shared:20 - nfs4 nas:/export rw,vers=4.2
+41 28 0:36 / /media/My\\040Drive rw,nosuid - fuse.rclone drive: rw,user_id=1000
+42 28 8:17 / /media/usb rw,relatime - fuseblk /dev/sdb1 rw,user_id=0
+";
+
+#[test]
+fn mountinfo_is_parsed() {
+    let mounts = parse_mountinfo(MOUNTINFO);
+    assert_eq!(mounts.len(), 5);
+
+    let by_point = |p: &str| mounts.iter().find(|m| m.mount_point == Path::new(p)).unwrap();
+
+    assert_eq!(by_point("/").fs_type, "ext4");
+    assert!(!by_point("/").needs_polling());
+    assert!(by_point("/mnt/nas").needs_polling());
+    assert!(by_point("/media/My Drive").needs_polling());
+    assert!(!by_point("/media/usb").needs_polling());
+}
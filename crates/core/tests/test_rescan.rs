@@ -0,0 +1,164 @@
+use std::{fs, sync::Arc, thread, time::Duration};
+
+use lunio_core::{EngineRuntime, engine::{config::RootConfig, jobs::JobState, roots::RootStatus}, fs::{scan::{ScanOptions, ScanProgress, scan_root_with}, watcher::{FsChange, WatchIssue}}};
+
+#[test]
+fn rescan_only_touches_its_subtree() {
+    let root = std::env::temp_dir().join(format!("lunio-rescan-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("a")).unwrap();
+    fs::create_dir_all(root.join("b")).unwrap();
+    fs::write(root.join("a/keep.txt"), b"").unwrap();
+    fs::write(root.join("a/gone.txt"), b"").unwrap();
+    fs::write(root.join("a/grow.txt"), b"").unwrap();
+    fs::write(root.join("b/other.txt"), b"").unwrap();
+
+    let engine = EngineRuntime::new(root.join(".cache"), None, None);
+    engine.full_scan(root.join("a"));
+    engine.full_scan(root.join("b"));
+    assert_eq!(engine.indexed_count(), 6);
+
+    // Created before the removal so the new file cannot reuse its inode.
+    fs::write(root.join("a/new.txt"), b"").unwrap();
+    fs::remove_file(root.join("a/gone.txt")).unwrap();
+    fs::write(root.join("a/grow.txt"), b"bigger now").unwrap();
+
+    let changes = engine.full_scan(root.join("a"));
+    let count = |f: fn(&FsChange) -> bool| changes.iter().filter(|c| f(c)).count();
+
+    assert_eq!(count(|c| matches!(c, FsChange::Created(..))), 1);
+    assert_eq!(count(|c| matches!(c, FsChange::Deleted(..))), 1);
+
+    // `a` itself changed mtime along with `grow.txt`.
+    let modified: Vec<_> = changes.iter()
+        .filter_map(|c| match c {
+            FsChange::Modified(_, meta) => meta.path.file_name(),
+            _ => None
+        })
+        .collect();
+    assert!(modified.contains(&"grow.txt".as_ref()));
+    assert!(!modified.contains(&"keep.txt".as_ref()));
+
+    assert_eq!(engine.indexed_count(), 6);
+    assert_eq!(engine.search("other", 10).len(), 1);
+    assert!(engine.search("gone", 10).is_empty());
+    assert!(engine.full_scan(root.join("a")).is_empty());
+
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn scan_jobs_report_progress() {
+    let root = std::env::temp_dir().join(format!("lunio-jobs-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("tree/sub")).unwrap();
+    fs::write(root.join("tree/one.txt"), b"").unwrap();
+    fs::write(root.join("tree/sub/two.txt"), b"").unwrap();
+
+    let engine = EngineRuntime::new(root.join(".cache"), None, None);
+    let job = engine.start_scan(root.join("tree")).unwrap();
+    assert!(engine.start_scan(root.join("missing")).is_err());
+
+    let mut info = engine.scan_status(job.id).unwrap();
+    for _ in 0..100 {
+        if info.state != JobState::Running {
+            break;
+        }
+        thread::sleep(Duration::from_millis(20));
+        info = engine.scan_status(job.id).unwrap();
+    }
+
+    assert_eq!(info.state, JobState::Completed);
+    assert_eq!((info.dirs, info.files, info.errors), (2, 2, 0));
+    assert_eq!(engine.search("two", 10).len(), 1);
+    assert!(!engine.cancel_scan(job.id));
+
+    let progress = Arc::new(ScanProgress::default());
+    progress.cancel();
+    let opts = ScanOptions { progress: Some(progress), ..Default::default() };
+    assert!(scan_root_with(root.join("tree"), &opts).is_empty());
+
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn lost_events_resync_the_root() {
+    let root = std::env::temp_dir().join(format!("lunio-overflow-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("tree")).unwrap();
+    fs::write(root.join("tree/old.txt"), b"").unwrap();
+
+    let engine = EngineRuntime::new(root.join(".cache"), None, None);
+    let tree = root.join("tree");
+    let mut config = RootConfig::new(tree.clone());
+    config.watch = false;
+    engine.add_root(config).unwrap();
+
+    let status = || engine.root_info(&tree).unwrap().status;
+    let wait_ready = || {
+        for _ in 0..100 {
+            if status() == RootStatus::Ready {
+                return;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+        panic!("root never became ready");
+    };
+    wait_ready();
+
+    // Changes nobody heard about, as after an overflowed event queue.
+    fs::write(root.join("tree/new.txt"), b"").unwrap();
+    fs::remove_file(root.join("tree/old.txt")).unwrap();
+
+    for _ in 0..10 {
+        engine.report_watch_issue(&tree, WatchIssue::Rescan);
+        assert_eq!(status(), RootStatus::Scanning);
+    }
+    wait_ready();
+
+    assert_eq!(engine.search("new.txt", 10).len(), 1);
+    assert!(engine.search("old.txt", 10).is_empty());
+
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn every_scan_is_a_job() {
+    let root = std::env::temp_dir().join(format!("lunio-scan-jobs-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("big")).unwrap();
+    fs::create_dir_all(root.join("plain")).unwrap();
+    for i in 0..5000 {
+        fs::write(root.join(format!("big/{i}.txt")), b"").unwrap();
+    }
+    fs::write(root.join("plain/one.txt"), b"").unwrap();
+
+    let engine = EngineRuntime::new(root.join(".cache"), None, None);
+    let big = root.join("big");
+    let mut config = RootConfig::new(big.clone());
+    config.watch = false;
+    engine.add_root(config).unwrap();
+
+    // A root's scan can be cancelled like any other.
+    let job = engine.list_scans().into_iter().find(|j| j.root == big).unwrap();
+    assert!(engine.cancel_scan(job.id));
+
+    let mut info = engine.scan_status(job.id).unwrap();
+    for _ in 0..100 {
+        if info.state != JobState::Running {
+            break;
+        }
+        thread::sleep(Duration::from_millis(20));
+        info = engine.scan_status(job.id).unwrap();
+    }
+    assert_eq!(info.state, JobState::Cancelled);
+    assert_eq!(engine.root_info(&big).unwrap().status, RootStatus::Pending);
+    assert_eq!(engine.indexed_count(), 0);
+
+    // So is the scan behind listing a directory nothing has indexed yet.
+    assert_eq!(engine.list_dir(&root.join("plain")).len(), 1);
+    let listed = engine.list_scans().into_iter().find(|j| j.root == root.join("plain")).unwrap();
+    assert_eq!(listed.state, JobState::Completed);
+
+    let _ = fs::remove_dir_all(&root);
+}
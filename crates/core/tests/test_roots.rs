@@ -0,0 +1,118 @@
+use std::{fs, path::{Path, PathBuf}, sync::{Arc, Mutex}, thread, time::Duration};
+
+use lunio_core::{EngineRuntime, engine::{config::RootConfig, events::EngineEvent, roots::RootStatus}};
+
+fn fixture(name: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("lunio-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("home/node_modules/pkg")).unwrap();
+    fs::create_dir_all(root.join("home/deep/er/still")).unwrap();
+    fs::create_dir_all(root.join("media")).unwrap();
+    fs::write(root.join("home/node_modules/pkg/index.js"), b"").unwrap();
+    fs::write(root.join("home/deep/er/still/buried.txt"), b"").unwrap();
+    fs::write(root.join("home/todo.txt"), b"").unwrap();
+    fs::write(root.join("media/song.mp3"), b"").unwrap();
+    root
+}
+
+fn wait_ready(engine: &EngineRuntime, path: &Path) {
+    for _ in 0..100 {
+        if engine.root_info(path).is_some_and(|r| r.status == RootStatus::Ready) {
+            return;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    panic!("root {:?} never became ready", path);
+}
+
+#[test]
+fn roots_are_indexed_independently() {
+    let root = fixture("roots");
+    let engine = EngineRuntime::new(root.join(".cache"), None, None);
+
+    let mut home = RootConfig::new(root.join("home"));
+    home.exclude = vec!["node_modules".into()];
+    home.max_depth = Some(2);
+    home.watch = false;
+    engine.add_root(home).unwrap();
+
+    let mut media = RootConfig::new(root.join("media"));
+    media.watch = false;
+    engine.add_root(media).unwrap();
+
+    wait_ready(&engine, &root.join("home"));
+    wait_ready(&engine, &root.join("media"));
+
+    assert_eq!(engine.list_roots().len(), 2);
+    assert_eq!(engine.search("todo", 10).len(), 1);
+    assert_eq!(engine.search("song", 10).len(), 1);
+    assert!(engine.search("index.js", 10).is_empty());
+    assert!(engine.search("buried", 10).is_empty());
+    assert!(engine.add_root(RootConfig::new(root.join("media"))).is_err());
+
+    engine.remove_root(&root.join("media")).unwrap();
+    assert!(engine.search("song", 10).is_empty());
+    assert_eq!(engine.search("todo", 10).len(), 1);
+
+    let reloaded = EngineRuntime::new(root.join(".cache"), None, None);
+    assert_eq!(reloaded.list_roots().len(), 1);
+
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn nested_roots_keep_what_the_other_covers() {
+    let root = fixture("nested-roots");
+    let engine = EngineRuntime::new(root.join(".cache"), None, None);
+
+    let add = |path: PathBuf| {
+        let mut config = RootConfig::new(path.clone());
+        config.watch = false;
+        engine.add_root(config).unwrap();
+        wait_ready(&engine, &path);
+    };
+
+    add(root.join("home"));
+    add(root.join("home/deep"));
+    assert_eq!(engine.search("buried", 10).len(), 1);
+
+    engine.remove_root(&root.join("home/deep")).unwrap();
+    assert_eq!(engine.search("buried", 10).len(), 1);
+
+    add(root.join("home/deep"));
+    engine.remove_root(&root.join("home")).unwrap();
+    assert_eq!(engine.search("buried", 10).len(), 1);
+    assert_eq!(engine.search("still", 10).len(), 1);
+    assert!(engine.search("todo", 10).is_empty());
+
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn removed_roots_leave_the_store_and_tell_subscribers() {
+    let root = fixture("removed-root");
+    let engine = EngineRuntime::new(root.join(".cache"), None, None);
+
+    let mut media = RootConfig::new(root.join("media"));
+    media.watch = false;
+    engine.add_root(media).unwrap();
+    wait_ready(&engine, &root.join("media"));
+
+    let deleted = Arc::new(Mutex::new(Vec::new()));
+    let seen = deleted.clone();
+    engine.subscribe(Box::new(move |event| {
+        if let EngineEvent::Deleted { path, .. } = event {
+            seen.lock().unwrap().push(path.clone());
+        }
+        true
+    }));
+
+    engine.remove_root(&root.join("media")).unwrap();
+    assert!(deleted.lock().unwrap().contains(&root.join("media/song.mp3")));
+
+    let warm = EngineRuntime::new(root.join(".cache"), None, None);
+    assert!(warm.load_index());
+    assert!(warm.search("song", 10).is_empty());
+
+    let _ = fs::remove_dir_all(&root);
+}
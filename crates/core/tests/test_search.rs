@@ -0,0 +1,106 @@
+use std::{fs, path::PathBuf};
+
+use lunio_core::{EngineRuntime, index::{fuzzy::fuzzy_match, query::{Query, QueryError}}, models::SearchMode};
+
+fn fixture(name: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("lunio-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("src/index")).unwrap();
+    fs::write(root.join("src/index/trigram.rs"), b"").unwrap();
+    fs::write(root.join("src/index/store.rs"), b"").unwrap();
+    fs::write(root.join("src/Lib.rs"), b"").unwrap();
+    fs::write(root.join("src/notes.md"), vec![0u8; 2048]).unwrap();
+    root
+}
+
+#[test]
+fn substring_search_works() {
+    let root = fixture("search");
+    fs::write(root.join("src/aaa.txt"), b"").unwrap();
+    let engine = EngineRuntime::new(root.join(".cache"), None, None);
+    engine.full_scan(root.join("src"));
+
+    let names = |query: &str| -> Vec<String> {
+        engine.search(query, 50)
+            .iter()
+            .map(|m| m.path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect()
+    };
+
+    assert_eq!(names("GRAM"), vec!["trigram.rs"]);
+    assert_eq!(names(".rs"), vec!["Lib.rs", "store.rs", "trigram.rs"]);
+    assert_eq!(names("ex"), vec!["index"]);
+    assert!(names("missing").is_empty());
+    assert_eq!(names("aaa"), vec!["aaa.txt"]);
+    assert!(names("aaaa").is_empty());
+    assert_eq!(engine.search("rs", 2).len(), 2);
+
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn fuzzy_matching_works() {
+    let m = fuzzy_match("tri", "trigram.rs").unwrap();
+    assert_eq!(m.ranges, vec![(0, 3)]);
+
+    let m = fuzzy_match("sr", "store.rs").unwrap();
+    assert_eq!(m.ranges, vec![(0, 1), (3, 4)]);
+
+    assert!(fuzzy_match("xyz", "store.rs").is_none());
+
+    let contiguous = fuzzy_match("rs", "store.rs").unwrap();
+    let scattered = fuzzy_match("rs", "rust_source").unwrap();
+    assert!(contiguous.score > scattered.score);
+}
+
+#[test]
+fn fuzzy_search_ranks_results() {
+    let root = fixture("fuzzy");
+    let engine = EngineRuntime::new(root.join(".cache"), None, None);
+    engine.full_scan(root.join("src"));
+
+    let hits = engine.search_with("trs", 10, SearchMode::Fuzzy).unwrap();
+    let names: Vec<_> = hits.iter()
+        .map(|h| h.meta.path.file_name().unwrap().to_string_lossy().into_owned())
+        .collect();
+
+    assert_eq!(names, vec!["trigram.rs", "store.rs"]);
+    assert!(hits[0].score.is_some());
+    assert!(!hits[0].ranges.is_empty());
+
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn structured_query_works() {
+    let root = fixture("query");
+    let engine = EngineRuntime::new(root.join(".cache"), None, None);
+    engine.full_scan(root.join("src"));
+
+    let names = |query: &str| -> Vec<String> {
+        engine.search_with(query, 50, SearchMode::Query)
+            .unwrap()
+            .iter()
+            .map(|h| h.meta.path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect()
+    };
+
+    assert_eq!(names("ext:md"), vec!["notes.md"]);
+    assert_eq!(names("size:>1KB modified:<1d"), vec!["notes.md"]);
+    assert_eq!(names("ext:rs path:index -store"), vec!["trigram.rs"]);
+    assert_eq!(names("kind:dir OR \"lib.rs\""), vec!["src", "Lib.rs", "index"]);
+    assert_eq!(names("(tri OR sto) AND NOT ext:md"), vec!["store.rs", "trigram.rs"]);
+    assert!(names("modified:>1d").is_empty());
+
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn query_errors_are_reported() {
+    assert_eq!(Query::parse("\"open"), Err(QueryError::UnterminatedQuote(1)));
+    assert_eq!(Query::parse("(ext:pdf"), Err(QueryError::UnclosedGroup(1)));
+    assert_eq!(Query::parse("foo OR"), Err(QueryError::DanglingOperator("OR".into())));
+    assert_eq!(Query::parse("owner:me"), Err(QueryError::UnknownFilter("owner".into())));
+    assert!(matches!(Query::parse("size:>10XB"), Err(QueryError::InvalidValue { filter: "size", .. })));
+    assert!(Query::parse("modified:>=2024-01-31 ext:pdf").is_ok());
+}
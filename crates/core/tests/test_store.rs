@@ -0,0 +1,43 @@
+use std::{fs, path::PathBuf};
+
+use lunio_core::EngineRuntime;
+
+fn fixture(name: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("lunio-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("docs")).unwrap();
+    fs::write(root.join("docs/notes.txt"), b"hello").unwrap();
+    fs::write(root.join("readme.md"), b"# readme").unwrap();
+    root
+}
+
+#[test]
+fn index_survives_restart() {
+    let root = fixture("store");
+    let cache = root.join(".cache");
+
+    let engine = EngineRuntime::new(cache.clone(), None, None);
+    engine.full_scan(root.join("docs"));
+    let before = engine.indexed_count();
+    engine.shutdown();
+
+    let warm = EngineRuntime::new(cache.clone(), None, None);
+    assert!(warm.load_index());
+    assert_eq!(warm.indexed_count(), before);
+
+    // Changes made while nothing was running.
+    fs::write(root.join("docs/notes.txt"), b"hello, world").unwrap();
+    fs::create_dir_all(root.join("docs/later")).unwrap();
+    fs::write(root.join("docs/later/added.txt"), b"").unwrap();
+    fs::write(root.join("docs/new.txt"), b"").unwrap();
+    warm.reconcile_in_background().join().unwrap();
+
+    let notes = warm.search("notes", 10);
+    assert_eq!(notes.len(), 1);
+    assert_eq!(notes[0].size, 12);
+    assert_eq!(warm.search("new.txt", 10).len(), 1);
+    assert_eq!(warm.search("added", 10).len(), 1);
+
+    warm.shutdown();
+    let _ = fs::remove_dir_all(&root);
+}
@@ -1,6 +1,6 @@
-use std::path::Path;
+use std::{fs, path::Path, sync::Arc, thread, time::Duration};
 
-use lunio_core::{EngineRuntime, fs::id::generate_file_id};
+use lunio_core::{EngineRuntime, engine::events::EventBus, fs::id::generate_file_id, models::{FileId, FileKind, FileMeta}, thumbnails::{cache::ThumbnailCache, generator::ThumbnailConfig, worker::{LOW_QUEUE, ThumbnailPriority, ThumbnailWorker}}};
 
 #[test]
 fn thumbnail_works() {
@@ -10,7 +10,7 @@ fn thumbnail_works() {
 
     let path = Path::new("C:\\Users\\hreet\\Pictures\\WhatsApp Image 2025-08-09 at 18.40.39 (1).jpeg");
 
-    let id = generate_file_id(path);
+    let id = generate_file_id(path).unwrap();
 
     // ✅ Request generation
     engine.request_thumbnail(id);
@@ -25,3 +25,73 @@ fn thumbnail_works() {
 
     assert!(results.is_some());
 }
+
+#[test]
+fn thumbnails_are_fetched_in_batches() {
+    let root = std::env::temp_dir().join(format!("lunio-thumbs-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("grid")).unwrap();
+    image::RgbImage::new(32, 32).save(root.join("grid/tile.png")).unwrap();
+    fs::write(root.join("grid/notes.txt"), b"").unwrap();
+
+    let engine = EngineRuntime::new(root.join(".cache"), None, None);
+    engine.full_scan(root.join("grid"));
+
+    let tile = generate_file_id(&root.join("grid/tile.png")).unwrap();
+    let notes = generate_file_id(&root.join("grid/notes.txt")).unwrap();
+    let ids = [tile, notes, FileId(7)];
+
+    let first = engine.request_thumbnails(&ids, ThumbnailPriority::High);
+    assert!(!first.missing.contains(&tile));
+    assert!(first.missing.contains(&FileId(7)));
+
+    let mut batch = engine.get_thumbnails(&ids);
+    for _ in 0..100 {
+        if batch.pending.is_empty() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(20));
+        batch = engine.get_thumbnails(&ids);
+    }
+
+    assert_eq!(batch.ready.iter().map(|(id, _)| *id).collect::<Vec<_>>(), vec![tile]);
+    assert_eq!(batch.missing, vec![notes, FileId(7)]);
+
+    engine.shutdown();
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn low_priority_requests_are_capped() {
+    let root = std::env::temp_dir().join(format!("lunio-low-queue-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+    image::RgbImage::new(2000, 2000).save(root.join("big.png")).unwrap();
+
+    let cache = Arc::new(ThumbnailCache::new(ThumbnailConfig::new(root.join(".cache"), None, None)));
+    let worker = ThumbnailWorker::new(cache, Default::default(), Arc::new(EventBus::default()));
+
+    let meta = |id: u128, name: &str| FileMeta {
+        version: 1,
+        id: FileId(id),
+        path: root.join(name),
+        size: 0,
+        kind: FileKind::File,
+        modified: None,
+        created: None,
+        has_thumbnail: false
+    };
+
+    // Keeps the worker busy while the low lane fills up.
+    worker.submit_with(meta(0, "big.png"), ThumbnailPriority::High);
+    for id in 1..=1000 {
+        worker.submit_with(meta(id, &format!("gone-{id}.png")), ThumbnailPriority::Low);
+    }
+
+    let waiting = (0..=1000).filter(|&id| worker.is_pending(FileId(id))).count();
+    assert!(waiting <= LOW_QUEUE + 1, "{waiting} requests still pending");
+    assert!(worker.is_pending(FileId(1000)));
+
+    worker.shutdown();
+    let _ = fs::remove_dir_all(&root);
+}
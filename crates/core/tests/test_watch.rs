@@ -0,0 +1,218 @@
+use std::{fs, path::{Path, PathBuf}, sync::{Arc, Mutex, atomic::{AtomicUsize, Ordering}, mpsc}, thread, time::Duration};
+
+use lunio_core::{EngineRuntime, engine::{config::{RootConfig, WatchMode}, events::{EngineEvent, EventBus}, queue::ChangeQueue, roots::RootStatus}, fs::watcher::FsChange, models::{FileId, FileKind, FileMeta}};
+
+fn wait_for(what: &str, mut check: impl FnMut() -> bool) {
+    for _ in 0..150 {
+        if check() {
+            return;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    panic!("timed out waiting for {what}");
+}
+
+fn indexed_at(engine: &EngineRuntime, name: &str) -> Vec<String> {
+    engine.search(name, 10)
+        .iter()
+        .map(|m| m.path.to_string_lossy().into_owned())
+        .collect()
+}
+
+#[test]
+fn renames_and_deletes_reach_the_index() {
+    let root = std::env::temp_dir().join(format!("lunio-watch-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("tree/album/disc")).unwrap();
+    fs::write(root.join("tree/album/disc/track.flac"), b"").unwrap();
+    fs::write(root.join("tree/draft.txt"), b"").unwrap();
+    fs::write(root.join("tree/trash.txt"), b"").unwrap();
+
+    let engine = EngineRuntime::new(root.join(".cache"), None, None);
+    let tree = root.join("tree");
+    engine.add_root(RootConfig::new(tree.clone())).unwrap();
+    wait_for("initial scan", || engine.root_info(&tree).is_some_and(|r| r.status == RootStatus::Ready));
+
+    let has = |engine: &EngineRuntime, name: &str, path: &Path| {
+        indexed_at(engine, name) == vec![path.to_string_lossy().into_owned()]
+    };
+
+    fs::rename(tree.join("draft.txt"), tree.join("final.txt")).unwrap();
+    wait_for("file rename", || has(&engine, "final.txt", &tree.join("final.txt")));
+    assert!(engine.search("draft", 10).is_empty());
+
+    fs::rename(tree.join("album"), tree.join("record")).unwrap();
+    wait_for("directory rename", || has(&engine, "track.flac", &tree.join("record/disc/track.flac")));
+    assert!(has(&engine, "disc", &tree.join("record/disc")));
+
+    fs::remove_file(tree.join("trash.txt")).unwrap();
+    wait_for("deletion", || engine.search("trash", 10).is_empty());
+
+    fs::rename(tree.join("record"), root.join("elsewhere")).unwrap();
+    wait_for("move out of the tree", || engine.search("track", 10).is_empty());
+
+    fs::rename(root.join("elsewhere"), tree.join("returned")).unwrap();
+    wait_for("move into the tree", || has(&engine, "track.flac", &tree.join("returned/disc/track.flac")));
+
+    // Build tools write and remove scratch files faster than the debounce
+    // window, before the index has ever seen them.
+    fs::write(tree.join("marker.txt"), b"").unwrap();
+    fs::write(tree.join("ephemeral.tmp"), b"").unwrap();
+    thread::sleep(Duration::from_millis(30));
+    fs::remove_file(tree.join("ephemeral.tmp")).unwrap();
+    wait_for("marker", || has(&engine, "marker.txt", &tree.join("marker.txt")));
+    thread::sleep(Duration::from_millis(300));
+    assert!(engine.search("ephemeral", 10).is_empty());
+
+    engine.shutdown();
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn edited_images_get_new_thumbnails() {
+    let root = std::env::temp_dir().join(format!("lunio-watch-thumb-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("photos")).unwrap();
+    let photo = root.join("photos/cat.png");
+    image::RgbImage::new(32, 32).save(&photo).unwrap();
+
+    let engine = EngineRuntime::new(root.join(".cache"), None, None);
+    let photos = root.join("photos");
+    engine.add_root(RootConfig::new(photos.clone())).unwrap();
+    wait_for("initial scan", || engine.root_info(&photos).is_some_and(|r| r.status == RootStatus::Ready));
+
+    let id = engine.search("cat.png", 1)[0].id;
+    assert!(engine.request_thumbnail(id));
+    wait_for("first thumbnail", || engine.get_thumbnail(id).is_some());
+    let before = engine.get_thumbnail(id).unwrap();
+
+    image::RgbImage::from_pixel(32, 32, image::Rgb([255, 255, 255])).save(&photo).unwrap();
+    wait_for("new thumbnail", || engine.get_thumbnail(id).is_some_and(|t| t != before));
+
+    engine.shutdown();
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn polling_roots_pick_up_changes() {
+    let root = std::env::temp_dir().join(format!("lunio-poll-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("share")).unwrap();
+
+    let engine = EngineRuntime::new(root.join(".cache"), None, None);
+    let share = root.join("share");
+    let mut config = RootConfig::new(share.clone());
+    config.watch_mode = WatchMode::Poll;
+    config.poll_interval_secs = 1;
+    engine.add_root(config).unwrap();
+    wait_for("initial scan", || engine.root_info(&share).is_some_and(|r| r.status == RootStatus::Ready));
+    assert!(engine.root_info(&share).unwrap().polling);
+
+    fs::write(share.join("report.pdf"), b"").unwrap();
+    for _ in 0..5 {
+        if !engine.search("report", 10).is_empty() {
+            break;
+        }
+        thread::sleep(Duration::from_secs(1));
+    }
+    assert_eq!(engine.search("report", 10).len(), 1);
+
+    engine.shutdown();
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn subscribers_see_index_changes() {
+    let root = std::env::temp_dir().join(format!("lunio-events-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("notes")).unwrap();
+
+    let engine = EngineRuntime::new(root.join(".cache"), None, None);
+    let notes = root.join("notes");
+    engine.add_root(RootConfig::new(notes.clone())).unwrap();
+    wait_for("initial scan", || engine.root_info(&notes).is_some_and(|r| r.status == RootStatus::Ready));
+
+    let (tx, rx) = mpsc::channel();
+    let tx = Mutex::new(tx);
+    engine.subscribe(Box::new(move |event| tx.lock().unwrap().send(event.clone()).is_ok()));
+
+    // The directory's own mtime changes come and go with the debounce window.
+    let next = |what: &str| loop {
+        match rx.recv_timeout(Duration::from_secs(3)) {
+            Ok(EngineEvent::Modified(meta)) if meta.path == notes => continue,
+            Ok(event) => break event,
+            Err(_) => panic!("no event for {what}")
+        }
+    };
+
+    fs::write(notes.join("todo.md"), b"").unwrap();
+    assert!(matches!(next("create"), EngineEvent::Created(m) if m.path == notes.join("todo.md")));
+
+    fs::rename(notes.join("todo.md"), notes.join("done.md")).unwrap();
+    assert!(matches!(next("rename"), EngineEvent::Renamed { to, .. } if to == notes.join("done.md")));
+
+    fs::remove_file(notes.join("done.md")).unwrap();
+    assert!(matches!(next("delete"), EngineEvent::Deleted { path, .. } if path == notes.join("done.md")));
+
+    engine.shutdown();
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn subscribers_may_subscribe_from_a_callback() {
+    let bus = Arc::new(EventBus::default());
+    let calls = Arc::new(AtomicUsize::new(0));
+
+    let (inner_bus, inner_calls) = (bus.clone(), calls.clone());
+    bus.subscribe(Box::new(move |_| {
+        let calls = inner_calls.clone();
+        inner_bus.subscribe(Box::new(move |_| {
+            calls.fetch_add(1, Ordering::Relaxed);
+            true
+        }));
+        false
+    }));
+
+    let event = EngineEvent::Deleted { id: FileId(1), path: "/w/a".into() };
+    bus.publish(&event);
+    bus.publish(&event);
+
+    // The first subscriber left after one event; the one it added stays.
+    assert_eq!(calls.load(Ordering::Relaxed), 1);
+}
+
+fn meta(id: u128, path: &str, size: u64) -> FileMeta {
+    FileMeta {
+        version: 0,
+        id: FileId(id),
+        path: PathBuf::from(path),
+        size,
+        kind: FileKind::File,
+        modified: None,
+        created: None,
+        has_thumbnail: false
+    }
+}
+
+#[test]
+fn changes_are_coalesced_per_file() {
+    let mut queue = ChangeQueue::new();
+
+    for size in 0..100 {
+        queue.push(FsChange::Modified(FileId(1), meta(1, "/w/build.log", size)));
+    }
+    queue.push(FsChange::Created(FileId(2), meta(2, "/w/tmp", 0)));
+    queue.push(FsChange::Modified(FileId(2), meta(2, "/w/tmp", 1)));
+    queue.push(FsChange::Deleted(FileId(2)));
+    queue.push(FsChange::Renamed { id: FileId(3), from: "/w/a".into(), to: "/w/b".into() });
+    queue.push(FsChange::Renamed { id: FileId(3), from: "/w/b".into(), to: "/w/c".into() });
+    queue.push(FsChange::Modified(FileId(3), meta(3, "/w/c", 5)));
+
+    let batch = queue.drain();
+    assert!(queue.is_empty());
+    assert_eq!(batch.len(), 3);
+
+    assert!(matches!(&batch[0], FsChange::Modified(_, m) if m.size == 99));
+    assert!(matches!(&batch[1], FsChange::Renamed { from, to, .. } if from == Path::new("/w/a") && to == Path::new("/w/c")));
+    assert!(matches!(&batch[2], FsChange::Modified(FileId(3), _)));
+}
@@ -0,0 +1,108 @@
+use std::{fs, io::Write, path::{Path, PathBuf}};
+
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+
+/// A secret shared with local clients through a file only the current user
+/// can read. Knowing it is what lets a TCP connection in.
+pub struct Token {
+    secret: String,
+    path: PathBuf
+}
+
+impl Token {
+    /// Generates a fresh token and writes it to `path`, replacing any left
+    /// over from an earlier run. The file is removed again on drop.
+    pub fn create(path: &Path) -> Result<Self> {
+        let secret = hex(&random_bytes::<32>()?);
+
+        if let Some(dir) = path.parent() {
+            create_private_dir(dir)?;
+        }
+
+        let _ = fs::remove_file(path);
+        private_file(path)?.write_all(secret.as_bytes())?;
+
+        Ok(Self { secret, path: path.to_path_buf() })
+    }
+
+    /// Checks a client's proof, the hex SHA-256 of `"{challenge}:{token}"`;
+    /// the token itself never crosses the wire.
+    pub fn verify(&self, challenge: &str, proof: &str) -> bool {
+        let expected = prove(challenge, &self.secret);
+
+        // Compared in full either way so timing says nothing about the token.
+        expected.len() == proof.len()
+            && expected.bytes().zip(proof.bytes()).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+    }
+}
+
+impl Drop for Token {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+pub fn new_challenge() -> Result<String> {
+    Ok(hex(&random_bytes::<16>()?))
+}
+
+fn prove(challenge: &str, secret: &str) -> String {
+    format!("{:x}", Sha256::digest(format!("{challenge}:{secret}")))
+}
+
+fn random_bytes<const N: usize>() -> Result<[u8; N]> {
+    let mut buf = [0u8; N];
+    getrandom::fill(&mut buf).map_err(|e| anyhow::anyhow!("no randomness available: {e}"))?;
+    Ok(buf)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Creates `dir` for files only the current user may touch. One that
+/// already exists must be a directory of this user's with mode 0700, or
+/// someone else could have made it first to swap in their own files.
+#[cfg(unix)]
+pub fn create_private_dir(dir: &Path) -> std::io::Result<()> {
+    use std::{io::{Error, ErrorKind}, os::unix::fs::{DirBuilderExt, MetadataExt}};
+
+    if let Some(parent) = dir.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    match fs::DirBuilder::new().mode(0o700).create(dir) {
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
+        res => return res
+    }
+
+    let meta = fs::symlink_metadata(dir)?;
+    let uid = lunio_protocol::paths::uid();
+
+    if !meta.is_dir() || meta.uid() != uid || meta.mode() & 0o777 != 0o700 {
+        return Err(Error::new(
+            ErrorKind::PermissionDenied,
+            format!("{} must be a directory owned by uid {uid} with mode 0700", dir.display())
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn create_private_dir(dir: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(dir)
+}
+
+#[cfg(unix)]
+fn private_file(path: &Path) -> std::io::Result<fs::File> {
+    use std::os::unix::fs::OpenOptionsExt;
+    fs::OpenOptions::new().write(true).create_new(true).mode(0o600).open(path)
+}
+
+/// The user's profile directory is already closed to other users.
+#[cfg(not(unix))]
+fn private_file(path: &Path) -> std::io::Result<fs::File> {
+    fs::OpenOptions::new().write(true).create_new(true).open(path)
+}
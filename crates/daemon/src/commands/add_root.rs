@@ -0,0 +1,37 @@
+use std::{path::PathBuf, sync::Arc};
+
+use lunio_core::{EngineRuntime, engine::config::RootConfig};
+
+use crate::{commands::blocking, protocol::{Response, ResponseData, RootOptions, root_entry, watch_mode}};
+
+pub async fn handle_add_root(engine: Arc<EngineRuntime>, path: String, options: RootOptions) -> Response {
+    if path.trim().is_empty() {
+        return Response::Error { message: "Root path cannot be empty".into() };
+    }
+
+    let mut config = RootConfig::new(PathBuf::from(path));
+    config.exclude = options.exclude;
+    config.max_depth = options.max_depth;
+    if let Some(watch) = options.watch {
+        config.watch = watch;
+    }
+    if let Some(include_hidden) = options.include_hidden {
+        config.include_hidden = include_hidden;
+    }
+    if let Some(follow_links) = options.follow_links {
+        config.follow_links = follow_links;
+    }
+    if let Some(mode) = options.watch_mode {
+        config.watch_mode = watch_mode(mode);
+    }
+    if let Some(secs) = options.poll_interval_secs {
+        config.poll_interval_secs = secs;
+    }
+
+    // Setting up the watcher walks the whole tree.
+    match blocking(&engine, move |engine| engine.add_root(config)).await {
+        Ok(Ok(info)) => Response::Ok { data: Some(ResponseData::Roots { roots: vec![root_entry(info)] }) },
+        Ok(Err(e)) => Response::Error { message: e.to_string() },
+        Err(failed) => failed
+    }
+}
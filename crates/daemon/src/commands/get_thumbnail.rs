@@ -1,9 +1,8 @@
 use std::sync::Arc;
 
-use base64::{Engine, engine::general_purpose};
 use lunio_core::EngineRuntime;
 
-use crate::protocol::{Response, ResponseData};
+use crate::{commands::blocking, protocol::{Response, ResponseData}};
 
 pub async fn handle_get_thumbnail(engine: Arc<EngineRuntime>, id_hex: String) -> Response {
     let id = match u128::from_str_radix(&id_hex, 16) {
@@ -11,11 +10,9 @@ pub async fn handle_get_thumbnail(engine: Arc<EngineRuntime>, id_hex: String) ->
         Err(_) => return Response::Error { message: "Invalid file id".into() }
     };
 
-    match engine.get_thumbnail(id) {
-        Some(bytes) => {
-            let encoded = general_purpose::STANDARD.encode(bytes);
-            Response::Ok { data: Some(ResponseData::Thumbnail { id: id_hex, bytes: encoded }) }
-        },
-        None => Response::Error { message: "thumbnail not available".into() }
+    match blocking(&engine, move |engine| engine.get_thumbnail(id)).await {
+        Ok(Some(bytes)) => Response::Binary { data: ResponseData::Thumbnail { id: id_hex }, bytes },
+        Ok(None) => Response::Error { message: "thumbnail not available".into() },
+        Err(failed) => failed
     }
 }
\ No newline at end of file
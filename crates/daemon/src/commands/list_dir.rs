@@ -1,26 +1,87 @@
-use std::{path::Path, sync::Arc};
+use std::{path::PathBuf, sync::Arc};
 
-use lunio_core::EngineRuntime;
+use lunio_core::{EngineRuntime, engine::listing::ListOptions, models::FileMeta};
 
-use crate::protocol::{DaemonFileEntry, Response, ResponseData};
+use crate::{commands::blocking, protocol::{FileEntry, Response, ResponseData, file_entry}, session::Session};
 
-pub async fn handle_list_dir(engine: Arc<EngineRuntime>, path: String) -> Response {
-    let path = Path::new(&path);
+/// Entries per frame of a streamed listing.
+pub const CHUNK_SIZE: usize = 1000;
 
-    let entries = engine.list_dir(path);
+/// Which part of a directory to send, and in what order.
+pub struct ListWindow {
+    pub offset: usize,
+    pub limit: Option<usize>,
+    pub options: ListOptions
+}
 
-    let out: Vec<DaemonFileEntry> = entries.into_iter()
-        .map(|m| DaemonFileEntry {
-            id: format!("{:032x}", m.id.0),
-            path: m.path.to_string_lossy().into_owned(),
-            size: m.size,
-            is_dir: matches!(m.kind, lunio_core::models::FileKind::Directory),
-            modified: m.modified
-                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-                .map(|d| d.as_secs() as i64),
-            has_thumbnail: m.has_thumbnail
-        })
+pub async fn handle_list_dir(engine: Arc<EngineRuntime>, session: &Session, path: String, window: ListWindow) -> Response {
+    let (total, window) = match list_window(&engine, session, &path, window).await {
+        Ok(listed) => listed,
+        Err(failed) => return failed
+    };
+
+    let entries: Vec<FileEntry> = window
+        .map(file_entry)
         .collect();
-    
-    Response::Ok { data: Some(ResponseData::DirectoryListing { entries: out }) }
-}
\ No newline at end of file
+
+    Response::Ok { data: Some(ResponseData::DirectoryListing { entries, total }) }
+}
+
+/// Sends the total first so the client can size its view, then the entries
+/// a chunk at a time; the returned `ListingEnd` closes the stream.
+pub async fn stream_list_dir(
+    engine: Arc<EngineRuntime>,
+    session: &Session,
+    id: Option<u64>,
+    path: String,
+    window: ListWindow
+) -> Response {
+    let (total, mut window) = match list_window(&engine, session, &path, window).await {
+        Ok(listed) => listed,
+        Err(failed) => return failed
+    };
+
+    session.send(id, Response::Ok { data: Some(ResponseData::ListingStart { total }) }).await;
+
+    loop {
+        let entries: Vec<FileEntry> = window.by_ref()
+            .take(CHUNK_SIZE)
+            .map(file_entry)
+            .collect();
+
+        if entries.is_empty() {
+            break;
+        }
+
+        // Waits while the connection's queue is full, so a slow reader holds
+        // the listing back instead of it piling up in memory.
+        if !session.send(id, Response::Ok { data: Some(ResponseData::ListingChunk { entries }) }).await {
+            break;
+        }
+    }
+
+    Response::Ok { data: Some(ResponseData::ListingEnd) }
+}
+
+/// `total` counts what is left after filtering, before the window is cut.
+/// Listing a directory nothing has indexed yet scans it first.
+async fn list_window(
+    engine: &Arc<EngineRuntime>,
+    session: &Session,
+    path: &str,
+    window: ListWindow
+) -> Result<(usize, impl Iterator<Item = FileMeta> + use<>), Response> {
+    let entries = match session.cached_listing(path, &window.options, window.offset) {
+        Some(entries) => entries,
+        None => {
+            let (dir, options) = (PathBuf::from(path), window.options.clone());
+            let listed = blocking(engine, move |engine| engine.list_dir_with(&dir, &options)).await?;
+            session.remember_listing(path, &window.options, listed)
+        }
+    };
+    let total = entries.len();
+
+    let end = window.offset.saturating_add(window.limit.unwrap_or(usize::MAX)).min(total);
+    let start = window.offset.min(end);
+    Ok((total, (start..end).map(move |i| entries[i].clone())))
+}
@@ -0,0 +1,14 @@
+use std::sync::Arc;
+
+use lunio_core::EngineRuntime;
+
+use crate::protocol::{Response, ResponseData, root_entry};
+
+pub async fn handle_list_roots(engine: Arc<EngineRuntime>) -> Response {
+    let roots = engine.list_roots()
+        .into_iter()
+        .map(root_entry)
+        .collect();
+
+    Response::Ok { data: Some(ResponseData::Roots { roots }) }
+}
\ No newline at end of file
@@ -1,7 +1,29 @@
+use std::sync::Arc;
+
+use lunio_core::EngineRuntime;
+
+use crate::protocol::Response;
+
 pub mod scan;
 pub mod search;
 pub mod shutdown;
 pub mod list_dir;
 pub mod request_thumbnail;
 pub mod get_thumbnail;
-pub mod open_file;
\ No newline at end of file
+pub mod open_file;
+pub mod add_root;
+pub mod remove_root;
+pub mod list_roots;
+pub mod subscribe;
+pub mod thumbnails;
+
+/// Runs engine work that may scan, search or touch the disk on the blocking
+/// pool, so it cannot hold up the runtime's workers and with them every
+/// other request and connection.
+pub async fn blocking<T: Send + 'static>(engine: &Arc<EngineRuntime>, work: impl FnOnce(&EngineRuntime) -> T + Send + 'static) -> Result<T, Response> {
+    let engine = engine.clone();
+
+    tokio::task::spawn_blocking(move || work(&engine))
+        .await
+        .map_err(|e| Response::Error { message: format!("request failed: {e}") })
+}
\ No newline at end of file
@@ -1,12 +1,15 @@
-use std::{path::Path, sync::Arc};
+use std::{path::PathBuf, sync::Arc};
 
 use lunio_core::EngineRuntime;
 
-use crate::protocol::Response;
+use crate::{commands::blocking, protocol::Response};
 
 pub async fn handle_open_file(engine: Arc<EngineRuntime>, path: String) -> Response {
-    match engine.open_file(Path::new(&path)) {
-        Ok(_) => Response::Ok { data: Some(crate::protocol::ResponseData::Ack) },
-        Err(e) => Response::Error { message: e.to_string() }
+    let path = PathBuf::from(path);
+
+    match blocking(&engine, move |engine| engine.open_file(&path)).await {
+        Ok(Ok(_)) => Response::Ok { data: Some(crate::protocol::ResponseData::Ack) },
+        Ok(Err(e)) => Response::Error { message: e.to_string() },
+        Err(failed) => failed
     }
 }
\ No newline at end of file
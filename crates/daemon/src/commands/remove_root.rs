@@ -0,0 +1,15 @@
+use std::{path::PathBuf, sync::Arc};
+
+use lunio_core::EngineRuntime;
+
+use crate::{commands::blocking, protocol::{Response, ResponseData}};
+
+pub async fn handle_remove_root(engine: Arc<EngineRuntime>, path: String) -> Response {
+    let path = PathBuf::from(path);
+
+    match blocking(&engine, move |engine| engine.remove_root(&path)).await {
+        Ok(Ok(_)) => Response::Ok { data: Some(ResponseData::Ack) },
+        Ok(Err(e)) => Response::Error { message: e.to_string() },
+        Err(failed) => failed
+    }
+}
\ No newline at end of file
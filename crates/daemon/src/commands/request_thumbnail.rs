@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use lunio_core::EngineRuntime;
 
-use crate::protocol::{Response, ResponseData};
+use crate::{commands::blocking, protocol::{Response, ResponseData}};
 
 pub async fn handle_request_thumbnail(engine: Arc<EngineRuntime>, id_hex: String) -> Response {
     let id = match u128::from_str_radix(&id_hex, 16) {
@@ -10,9 +10,9 @@ pub async fn handle_request_thumbnail(engine: Arc<EngineRuntime>, id_hex: String
         Err(_) => return Response::Error { message: "Invalid file id".into() }
     };
 
-    if engine.request_thumbnail(id) {
-        Response::Ok { data: Some(ResponseData::Ack) }
-    } else {
-        Response::Error { message: "file not found in index".into() }
+    match blocking(&engine, move |engine| engine.request_thumbnail(id)).await {
+        Ok(true) => Response::Ok { data: Some(ResponseData::Ack) },
+        Ok(false) => Response::Error { message: "file not found in index".into() },
+        Err(failed) => failed
     }
 }
\ No newline at end of file
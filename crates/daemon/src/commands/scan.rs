@@ -2,13 +2,35 @@ use std::sync::Arc;
 
 use lunio_core::EngineRuntime;
 
-use crate::protocol::{Response, ResponseData};
+use crate::protocol::{Response, ResponseData, scan_job};
 
 pub async fn handle_scan(engine: Arc<EngineRuntime>, root: String) -> Response {
     if root.trim().is_empty() {
         return Response::Error { message: "Root path cannot be empty".into() };
     }
 
-    engine.full_scan(root);
-    Response::Ok { data: Some(ResponseData::Ack) }
-}
\ No newline at end of file
+    match engine.start_scan(root) {
+        Ok(job) => Response::Ok { data: Some(ResponseData::ScanJobs { jobs: vec![scan_job(job)] }) },
+        Err(e) => Response::Error { message: e.to_string() }
+    }
+}
+
+pub async fn handle_scan_status(engine: Arc<EngineRuntime>, id: Option<u64>) -> Response {
+    let jobs = match id {
+        Some(id) => match engine.scan_status(id) {
+            Some(job) => vec![scan_job(job)],
+            None => return Response::Error { message: format!("Unknown scan job {id}") }
+        },
+        None => engine.list_scans().into_iter().map(scan_job).collect()
+    };
+
+    Response::Ok { data: Some(ResponseData::ScanJobs { jobs }) }
+}
+
+pub async fn handle_cancel_scan(engine: Arc<EngineRuntime>, id: u64) -> Response {
+    if engine.cancel_scan(id) {
+        Response::Ok { data: Some(ResponseData::Ack) }
+    } else {
+        Response::Error { message: format!("No running scan job {id}") }
+    }
+}
@@ -1,28 +1,24 @@
 use std::sync::Arc;
 
-use lunio_core::EngineRuntime;
+use lunio_core::{EngineRuntime, models::SearchMode};
 
-use crate::protocol::{DaemonFileEntry, Response, ResponseData};
+use crate::{commands::blocking, protocol::{Response, ResponseData, search_entry}};
 
 pub async fn handle_search(
     engine: Arc<EngineRuntime>,
     query: String,
-    limit: Option<usize>
+    limit: Option<usize>,
+    mode: SearchMode
 ) -> Response {
-    let results = engine.search(&query, limit.unwrap_or(50));
+    let results = match blocking(&engine, move |engine| engine.search_with(&query, limit.unwrap_or(50), mode)).await {
+        Ok(Ok(r)) => r,
+        Ok(Err(e)) => return Response::Error { message: format!("invalid query: {e}") },
+        Err(failed) => return failed
+    };
 
     let entries = results
         .into_iter()
-        .map(|m| DaemonFileEntry {
-            id: format!("{:032x}", m.id.0),
-            path: m.path.to_string_lossy().into_owned(),
-            size: m.size,
-            is_dir: matches!(m.kind, lunio_core::models::FileKind::Directory),
-            modified: m.modified
-                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-                .map(|d| d.as_secs() as i64),
-            has_thumbnail: m.has_thumbnail
-        })
+        .map(search_entry)
         .collect::<Vec<_>>();
 
     Response::Ok { data: Some(ResponseData::SearchResults { entries }) }
@@ -2,10 +2,13 @@ use std::{sync::Arc, time::Duration};
 
 use lunio_core::EngineRuntime;
 
-use crate::protocol::{Response, ResponseData};
+use crate::{commands::blocking, protocol::{Response, ResponseData}};
 
 pub async fn handle_shutdown(engine: Arc<EngineRuntime>) -> Response {
-    engine.shutdown();
+    if let Err(failed) = blocking(&engine, |engine| engine.shutdown()).await {
+        return failed;
+    }
+
     tokio::spawn(async {
         tokio::time::sleep(Duration::from_millis(100)).await;
         std::process::exit(0);
@@ -0,0 +1,26 @@
+use std::{path::PathBuf, sync::Arc};
+
+use lunio_core::{EngineRuntime, index::index::normalize};
+
+use crate::{protocol::{CAP_EVENTS, Response, ResponseData}, session::Session};
+
+pub async fn handle_subscribe(engine: Arc<EngineRuntime>, session: &Session, paths: Vec<String>) -> Response {
+    if !session.negotiated.supports(CAP_EVENTS) {
+        return Response::Error { message: format!("subscribing needs the \"{CAP_EVENTS}\" capability") };
+    }
+
+    if paths.iter().any(|p| p.trim().is_empty()) {
+        return Response::Error { message: "Subscription paths cannot be empty".into() };
+    }
+
+    // Engine events carry absolute paths without `.` or trailing separators.
+    let paths: Result<Vec<PathBuf>, _> = paths.iter().map(|p| std::path::absolute(p).map(|p| normalize(&p))).collect();
+    let paths = match paths {
+        Ok(paths) => paths,
+        Err(e) => return Response::Error { message: format!("Invalid subscription path: {e}") }
+    };
+
+    session.subscribe(&engine, paths);
+
+    Response::Ok { data: Some(ResponseData::Ack) }
+}
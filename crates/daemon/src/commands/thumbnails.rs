@@ -0,0 +1,54 @@
+use std::sync::Arc;
+
+use lunio_core::{EngineRuntime, models::{FileId, ThumbnailBatch}, thumbnails::worker::ThumbnailPriority};
+
+use crate::{commands::blocking, protocol::{Response, ResponseData, ThumbnailSlice, file_id}};
+
+pub async fn handle_get_thumbnails(engine: Arc<EngineRuntime>, ids: Vec<String>) -> Response {
+    let ids = match parse_ids(&ids) {
+        Ok(ids) => ids,
+        Err(message) => return Response::Error { message }
+    };
+
+    match blocking(&engine, move |engine| engine.get_thumbnails(&ids)).await {
+        Ok(batch) => batch_response(batch),
+        Err(failed) => failed
+    }
+}
+
+pub async fn handle_request_thumbnails(engine: Arc<EngineRuntime>, ids: Vec<String>, priority: ThumbnailPriority) -> Response {
+    let ids = match parse_ids(&ids) {
+        Ok(ids) => ids,
+        Err(message) => return Response::Error { message }
+    };
+
+    match blocking(&engine, move |engine| engine.request_thumbnails(&ids, priority)).await {
+        Ok(batch) => batch_response(batch),
+        Err(failed) => failed
+    }
+}
+
+fn parse_ids(ids: &[String]) -> Result<Vec<FileId>, String> {
+    ids.iter()
+        .map(|hex| u128::from_str_radix(hex, 16).map(FileId).map_err(|_| format!("Invalid file id {hex}")))
+        .collect()
+}
+
+fn batch_response(batch: ThumbnailBatch) -> Response {
+    let mut ready = Vec::with_capacity(batch.ready.len());
+    let mut bytes = Vec::new();
+
+    for (id, thumb) in batch.ready {
+        ready.push(ThumbnailSlice { id: file_id(id), len: thumb.len() });
+        bytes.extend_from_slice(&thumb);
+    }
+
+    Response::Binary {
+        data: ResponseData::Thumbnails {
+            ready,
+            pending: batch.pending.into_iter().map(file_id).collect(),
+            missing: batch.missing.into_iter().map(file_id).collect()
+        },
+        bytes
+    }
+}
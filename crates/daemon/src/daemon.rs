@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use lunio_core::EngineRuntime;
 
-use crate::{commands::{get_thumbnail::handle_get_thumbnail, list_dir::handle_list_dir, open_file::handle_open_file, request_thumbnail::handle_request_thumbnail, scan::handle_scan, search::handle_search, shutdown::handle_shutdown}, protocol::{Request, Response}};
+use crate::{commands::{add_root::handle_add_root, list_roots::handle_list_roots, remove_root::handle_remove_root, get_thumbnail::handle_get_thumbnail, list_dir::{ListWindow, handle_list_dir, stream_list_dir}, open_file::handle_open_file, request_thumbnail::handle_request_thumbnail, scan::{handle_cancel_scan, handle_scan, handle_scan_status}, search::handle_search, shutdown::handle_shutdown, subscribe::handle_subscribe, thumbnails::{handle_get_thumbnails, handle_request_thumbnails}}, protocol::{Request, Response, list_options, search_mode, thumbnail_priority}, session::Session};
 
 #[derive(Clone)]
 pub struct Daemon {
@@ -14,14 +14,30 @@ impl Daemon {
         Self { engine: Arc::new(engine) }
     }
 
-    pub async fn dispatch(&self, req: Request) -> Response {
+    /// Answers request `id`. Streaming handlers send their leading parts
+    /// through `session` and return the last one.
+    pub async fn dispatch(&self, id: Option<u64>, req: Request, session: &Session) -> Response {
         match req {
-            Request::Search { query, limit } => handle_search(self.engine.clone(), query, limit).await,
+            Request::Search { query, limit, mode } => handle_search(self.engine.clone(), query, limit, search_mode(mode)).await,
             Request::Scan { root } => handle_scan(self.engine.clone(), root).await,
-            Request::ListDir { path } => handle_list_dir(self.engine.clone(), path).await,
+            Request::ScanStatus { id } => handle_scan_status(self.engine.clone(), id).await,
+            Request::CancelScan { id } => handle_cancel_scan(self.engine.clone(), id).await,
+            Request::ListDir { path, offset, limit, stream, options } => {
+                let window = ListWindow { offset, limit, options: list_options(options) };
+                match stream {
+                    true => stream_list_dir(self.engine.clone(), session, id, path, window).await,
+                    false => handle_list_dir(self.engine.clone(), session, path, window).await
+                }
+            }
             Request::RequestThumbnail { id } => handle_request_thumbnail(self.engine.clone(), id).await,
             Request::GetThumbnail { id } => handle_get_thumbnail(self.engine.clone(), id).await,
+            Request::RequestThumbnails { ids, priority } => handle_request_thumbnails(self.engine.clone(), ids, thumbnail_priority(priority)).await,
+            Request::GetThumbnails { ids } => handle_get_thumbnails(self.engine.clone(), ids).await,
             Request::OpenFile { path } => handle_open_file(self.engine.clone(), path).await,
+            Request::AddRoot { path, options } => handle_add_root(self.engine.clone(), path, options).await,
+            Request::RemoveRoot { path } => handle_remove_root(self.engine.clone(), path).await,
+            Request::ListRoots => handle_list_roots(self.engine.clone()).await,
+            Request::Subscribe { paths } => handle_subscribe(self.engine.clone(), session, paths).await,
             Request::Shutdown => handle_shutdown(self.engine.clone()).await
         }
     }
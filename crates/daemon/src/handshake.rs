@@ -0,0 +1,97 @@
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use crate::{auth::{Token, new_challenge}, protocol::{CAPABILITIES, CAP_BINARY, ClientHello, Frame, Handshake, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION, Response, ResponseData, write_frame}, server::read_frame};
+
+/// How long a client has to say hello before it is dropped.
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What both sides agreed on for one connection.
+#[derive(Debug, Clone, Default)]
+pub struct Negotiated {
+    pub protocol: u8,
+    pub capabilities: Vec<String>
+}
+
+impl Negotiated {
+    pub fn supports(&self, capability: &str) -> bool {
+        self.capabilities.iter().any(|c| c == capability)
+    }
+}
+
+/// Greets a new connection and settles the protocol version and features,
+/// checking the client's proof when a token is required. Refused clients are
+/// told why before `None` is returned and the connection closed.
+pub async fn negotiate<R, W>(reader: &mut R, writer: &mut W, token: Option<&Token>) -> Option<Negotiated>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin
+{
+    let challenge = match token.map(|_| new_challenge()).transpose() {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("[lunio-daemon] {e}");
+            return None;
+        }
+    };
+
+    let greeting = Handshake {
+        protocol: PROTOCOL_VERSION,
+        min_protocol: MIN_PROTOCOL_VERSION,
+        engine: "lunio-daemon".into(),
+        capabilities: CAPABILITIES.iter().map(|c| c.to_string()).collect(),
+        challenge: challenge.clone()
+    };
+    write_json(writer, &greeting).await;
+
+    let answered = tokio::time::timeout(HANDSHAKE_TIMEOUT, read_frame(reader)).await.ok().flatten()?;
+
+    let result = match answered.parse() {
+        Ok(ClientHello::Hello { min_protocol, max_protocol, capabilities, proof }) => {
+            let authorized = match (token, &challenge) {
+                (Some(token), Some(challenge)) => proof.is_some_and(|p| token.verify(challenge, &p)),
+                _ => true
+            };
+            let protocol = max_protocol.min(PROTOCOL_VERSION);
+
+            if !authorized {
+                Err("authentication failed".to_string())
+            } else if protocol < min_protocol.max(MIN_PROTOCOL_VERSION) {
+                Err(format!(
+                    "incompatible protocol: daemon speaks {MIN_PROTOCOL_VERSION}-{PROTOCOL_VERSION}, client {min_protocol}-{max_protocol}"
+                ))
+            } else if !capabilities.iter().any(|c| c == CAP_BINARY) {
+                // Thumbnails have no other encoding, so every client needs it.
+                Err(format!("the \"{CAP_BINARY}\" capability is required"))
+            } else {
+                let capabilities = capabilities.into_iter()
+                    .filter(|c| CAPABILITIES.contains(&c.as_str()))
+                    .collect();
+                Ok(Negotiated { protocol, capabilities })
+            }
+        }
+        Err(_) => Err(format!(
+            "expected a hello; this daemon speaks protocol {MIN_PROTOCOL_VERSION}-{PROTOCOL_VERSION}, so the client needs updating"
+        ))
+    };
+
+    match result {
+        Ok(negotiated) => {
+            let welcome = ResponseData::Welcome {
+                protocol: negotiated.protocol,
+                capabilities: negotiated.capabilities.clone()
+            };
+            write_json(writer, &Response::Ok { data: Some(welcome) }).await;
+            Some(negotiated)
+        }
+        Err(message) => {
+            write_json(writer, &Response::Error { message }).await;
+            None
+        }
+    }
+}
+
+async fn write_json<W: AsyncWrite + Unpin>(writer: &mut W, value: &impl serde::Serialize) {
+    let _ = write_frame(writer, &Frame::json(value).unwrap()).await;
+}
@@ -0,0 +1,8 @@
+pub mod auth;
+pub mod protocol;
+pub mod daemon;
+pub mod handshake;
+pub mod server;
+pub mod session;
+pub mod commands;
+pub mod bootstrap;
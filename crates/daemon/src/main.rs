@@ -1,12 +1,28 @@
-mod protocol;
-mod daemon;
-mod server;
-mod commands;
-mod bootstrap;
-
-use lunio_core::EngineRuntime;
-
-use crate::{bootstrap::{bootstrap, load_manifest}, daemon::Daemon, server::start_server};
+use lunio_core::{EngineRuntime, engine::config::RootConfig};
+use lunio_daemon::{bootstrap::{bootstrap, load_manifest}, daemon::Daemon, server::{ServerOptions, start_server}};
+use lunio_protocol::{DEFAULT_TCP_ADDR, paths};
+
+/// `--tcp [ADDR]` additionally listens on TCP. Without a Unix socket to
+/// fall back on, TCP is always on.
+fn server_options() -> ServerOptions {
+    let mut args = std::env::args().skip(1).peekable();
+    let mut tcp = cfg!(not(unix)).then(|| DEFAULT_TCP_ADDR.to_string());
+
+    while let Some(arg) = args.next() {
+        if let Some(addr) = arg.strip_prefix("--tcp=") {
+            tcp = Some(addr.into());
+        } else if arg == "--tcp" {
+            let addr = args.next_if(|a| !a.starts_with("--"));
+            tcp = Some(addr.unwrap_or_else(|| DEFAULT_TCP_ADDR.into()));
+        }
+    }
+
+    ServerOptions {
+        #[cfg(unix)]
+        socket: paths::socket_path(),
+        tcp
+    }
+}
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -17,13 +33,29 @@ async fn main() -> anyhow::Result<()> {
 
     let runtime = bootstrap(runtime_root, manifest).await?;
 
-    let engine = EngineRuntime::new(".lunio-cache".into(), runtime.ffmpeg, runtime.pdfium);
+    let engine = EngineRuntime::new(paths::cache_dir(), runtime.ffmpeg, runtime.pdfium);
 
-    println!("[lunio-daemon] scanning...");
-    engine.full_scan(".");
+    if engine.load_index() {
+        println!("[lunio-daemon] loaded {} entries from disk, reconciling...", engine.indexed_count());
+        engine.reconcile_in_background();
+    }
+
+    engine.start_roots();
+
+    if engine.list_roots().is_empty()
+        && let Some(home) = dirs::home_dir()
+    {
+        println!("[lunio-daemon] no roots configured, indexing {}...", home.display());
+        engine.add_root(RootConfig::new(home))?;
+    }
 
     let daemon = Daemon::new(engine);
-    start_server(daemon).await?;
+    let options = server_options();
+
+    tokio::select! {
+        res = start_server(daemon.clone(), &options) => res?,
+        _ = tokio::signal::ctrl_c() => daemon.engine.shutdown()
+    }
 
     Ok(())
 }
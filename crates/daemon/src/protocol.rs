@@ -1,53 +1,171 @@
-use serde::{Deserialize, Serialize};
+use std::{path::PathBuf, time::SystemTime};
 
-pub const PROTOCOL_VERSION: u8 = 1;
+use lunio_core::{engine::{config, events::EngineEvent, jobs::{JobState, ScanJobInfo}, listing, roots::{RootInfo, RootStatus}}, models::{self, FileId, FileKind, FileMeta, SearchHit}, thumbnails::worker};
 
-#[derive(Debug, Deserialize)]
-#[serde(tag = "type")]
-pub enum Request {
-    Scan { root: String },
-    Search { query: String, limit: Option<usize> },
-    ListDir { path: String },
+pub use lunio_protocol::*;
 
-    RequestThumbnail { id: String },
-    GetThumbnail { id: String },
+// The wire types live in `lunio_protocol`, which knows nothing about the
+// engine, so the conversions between the two are plain functions here.
 
-    OpenFile { path: String },
+pub fn file_id(id: FileId) -> String {
+    format!("{:032x}", id.0)
+}
+
+pub fn file_entry(m: FileMeta) -> FileEntry {
+    let (kind, link_target, link_broken) = match &m.kind {
+        FileKind::File => ("file", None, false),
+        FileKind::Directory => ("directory", None, false),
+        FileKind::Symlink { target, broken } => ("symlink", Some(target.to_string_lossy().into_owned()), *broken),
+        FileKind::Other => ("other", None, false)
+    };
+
+    FileEntry {
+        id: file_id(m.id),
+        path: m.path.to_string_lossy().into_owned(),
+        size: m.size,
+        is_dir: matches!(m.kind, FileKind::Directory),
+        kind: kind.into(),
+        link_target,
+        link_broken,
+        modified: m.modified.and_then(unix_secs),
+        has_thumbnail: m.has_thumbnail,
+        score: None,
+        matches: Vec::new()
+    }
+}
+
+pub fn search_entry(hit: SearchHit) -> FileEntry {
+    let name = hit.meta.path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+
+    FileEntry {
+        score: hit.score,
+        matches: utf16_ranges(&name, hit.ranges),
+        ..file_entry(hit.meta)
+    }
+}
+
+/// The engine's match ranges count chars; the webview slices strings by
+/// UTF-16 code units.
+fn utf16_ranges(name: &str, ranges: Vec<(usize, usize)>) -> Vec<(usize, usize)> {
+    let offsets: Vec<usize> = std::iter::once(0)
+        .chain(name.chars().scan(0, |units, c| {
+            *units += c.len_utf16();
+            Some(*units)
+        }))
+        .collect();
+
+    ranges.into_iter().map(|(start, end)| (offsets[start], offsets[end])).collect()
+}
+
+pub fn root_entry(info: RootInfo) -> RootEntry {
+    let (status, error) = match info.status {
+        RootStatus::Pending => ("pending", None),
+        RootStatus::Scanning => ("scanning", None),
+        RootStatus::Ready => ("ready", None),
+        RootStatus::Failed(e) => ("failed", Some(e))
+    };
 
-    Shutdown
+    RootEntry {
+        path: info.config.path.to_string_lossy().into_owned(),
+        exclude: info.config.exclude,
+        max_depth: info.config.max_depth,
+        watch: info.config.watch,
+        include_hidden: info.config.include_hidden,
+        follow_links: info.config.follow_links,
+        watch_mode: match info.config.watch_mode {
+            config::WatchMode::Auto => WatchMode::Auto,
+            config::WatchMode::Native => WatchMode::Native,
+            config::WatchMode::Poll => WatchMode::Poll
+        },
+        poll_interval_secs: info.config.poll_interval_secs,
+        watching: info.watching,
+        polling: info.polling,
+        status: status.into(),
+        error,
+        entries: info.entries,
+        last_scan: info.last_scan.and_then(unix_secs),
+        warnings: info.warnings
+    }
 }
 
-#[derive(Debug, Serialize)]
-#[serde(tag = "status")]
-pub enum Response {
-    #[serde(rename = "ok")]
-    Ok { data: Option<ResponseData> },
+pub fn scan_job(job: ScanJobInfo) -> ScanJob {
+    ScanJob {
+        id: job.id,
+        root: job.root.to_string_lossy().into_owned(),
+        state: match job.state {
+            JobState::Running => "running",
+            JobState::Completed => "completed",
+            JobState::Cancelled => "cancelled",
+            JobState::Failed => "failed"
+        }.into(),
+        dirs: job.dirs,
+        files: job.files,
+        errors: job.errors,
+        elapsed_ms: job.elapsed.as_millis() as u64
+    }
+}
 
-    #[serde(rename = "error")]
-    Error { message: String },
+pub fn event(event: EngineEvent) -> Event {
+    let path = |p: PathBuf| p.to_string_lossy().into_owned();
+
+    match event {
+        EngineEvent::Created(meta) => Event::Created { entry: file_entry(meta) },
+        EngineEvent::Modified(meta) => Event::Modified { entry: file_entry(meta) },
+        EngineEvent::Deleted { id, path: p } => Event::Deleted { id: file_id(id), path: path(p) },
+        EngineEvent::Renamed { id, from, to } => Event::Renamed { id: file_id(id), from: path(from), to: path(to) },
+        EngineEvent::ThumbnailReady { id, path: p } => Event::ThumbnailReady { id: file_id(id), path: path(p) }
+    }
 }
 
-#[derive(Debug, Serialize)]
-#[serde(tag = "type")]
-pub enum ResponseData {
-    SearchResults { entries: Vec<DaemonFileEntry> },
-    DirectoryListing { entries: Vec<DaemonFileEntry> },
-    Thumbnail { id: String, bytes: String },
-    Ack
+pub fn search_mode(mode: SearchMode) -> models::SearchMode {
+    match mode {
+        SearchMode::Substring => models::SearchMode::Substring,
+        SearchMode::Fuzzy => models::SearchMode::Fuzzy,
+        SearchMode::Query => models::SearchMode::Query
+    }
 }
 
-#[derive(Debug, Serialize)]
-pub struct DaemonFileEntry {
-    pub id: String,
-    pub path: String,
-    pub size: u64,
-    pub is_dir: bool,
-    pub modified: Option<i64>,
-    pub has_thumbnail: bool
+pub fn watch_mode(mode: WatchMode) -> config::WatchMode {
+    match mode {
+        WatchMode::Auto => config::WatchMode::Auto,
+        WatchMode::Native => config::WatchMode::Native,
+        WatchMode::Poll => config::WatchMode::Poll
+    }
 }
 
-#[derive(Debug, Serialize)]
-pub struct Handshake {
-    pub protocol: u8,
-    pub engine: String
-}
\ No newline at end of file
+pub fn list_options(options: ListOptions) -> listing::ListOptions {
+    listing::ListOptions {
+        sort: match options.sort {
+            SortKey::Name => listing::SortKey::Name,
+            SortKey::Size => listing::SortKey::Size,
+            SortKey::Modified => listing::SortKey::Modified,
+            SortKey::Created => listing::SortKey::Created,
+            SortKey::Extension => listing::SortKey::Extension,
+            SortKey::Kind => listing::SortKey::Kind
+        },
+        direction: match options.direction {
+            SortDirection::Ascending => listing::SortDirection::Ascending,
+            SortDirection::Descending => listing::SortDirection::Descending
+        },
+        name_order: match options.name_order {
+            NameOrder::Ordinal => listing::NameOrder::Ordinal,
+            NameOrder::CaseInsensitive => listing::NameOrder::CaseInsensitive,
+            NameOrder::Natural => listing::NameOrder::Natural
+        },
+        folders_first: options.folders_first,
+        include_hidden: options.include_hidden,
+        extensions: options.extensions
+    }
+}
+
+pub fn thumbnail_priority(priority: ThumbnailPriority) -> worker::ThumbnailPriority {
+    match priority {
+        ThumbnailPriority::Low => worker::ThumbnailPriority::Low,
+        ThumbnailPriority::Normal => worker::ThumbnailPriority::Normal,
+        ThumbnailPriority::High => worker::ThumbnailPriority::High
+    }
+}
+
+fn unix_secs(t: SystemTime) -> Option<i64> {
+    t.duration_since(std::time::UNIX_EPOCH).ok().map(|d| d.as_secs() as i64)
+}
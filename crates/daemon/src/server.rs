@@ -1,67 +1,170 @@
-use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::{TcpListener, TcpStream}};
+use std::{path::PathBuf, sync::Arc};
+#[cfg(unix)]
+use std::{fs, io::ErrorKind, os::unix::fs::PermissionsExt, path::Path};
 
-use crate::{daemon::Daemon, protocol::{Handshake, PROTOCOL_VERSION, Request, Response}};
+#[cfg(unix)]
+use tokio::net::{UnixListener, UnixStream};
+use tokio::{io::{AsyncRead, AsyncWrite, WriteHalf}, net::TcpListener, sync::{Semaphore, mpsc::{Receiver, channel}}};
+
+use crate::{auth::{Token, create_private_dir}, daemon::Daemon, handshake::negotiate, protocol::{CodecError, Envelope, Frame, Response, paths, write_frame}, session::{FRAME_QUEUE, Session}};
 
 const MAX_PACKET: usize = 8 * 1024 * 1024;
 
-async fn handle_connection(daemon: Daemon, mut socket: TcpStream) {
-    let hello = Handshake {
-        protocol: PROTOCOL_VERSION,
-        engine: "lunio-daemon".into()
+/// Requests one connection may have running at once. Past that the next
+/// frame is not read until one finishes, which pushes back on the client.
+pub const MAX_IN_FLIGHT: usize = 16;
+
+/// Where clients reach the daemon.
+pub struct ServerOptions {
+    /// Only reachable by the user running the daemon.
+    #[cfg(unix)]
+    pub socket: PathBuf,
+    /// Reachable by every local user; off unless asked for on Unix.
+    pub tcp: Option<String>
+}
+
+/// Serves one connection until it closes. `token` is required of clients
+/// that cannot be trusted by the way they connected.
+pub async fn handle_connection<S>(daemon: Daemon, socket: S, token: Option<Arc<Token>>)
+where
+    S: AsyncRead + AsyncWrite + Send + 'static
+{
+    let (mut reader, mut writer) = tokio::io::split(socket);
+
+    let Some(negotiated) = negotiate(&mut reader, &mut writer, token.as_deref()).await else {
+        return;
     };
 
-    let hello_bytes = serde_json::to_vec(&hello).unwrap();
-    let _ = socket.write_u32(hello_bytes.len() as u32).await;
-    let _ = socket.write_all(&hello_bytes).await;
+    let (tx, rx) = channel(FRAME_QUEUE);
+    let session = Arc::new(Session::new(tx, negotiated));
+    let writer_task = tokio::spawn(write_frames(writer, rx));
+    let in_flight = Arc::new(Semaphore::new(MAX_IN_FLIGHT));
 
-    loop {
-        let len = match socket.read_u32().await {
-            Ok(l) => l as usize,
-            Err(_) => return
+    while let Some(frame) = read_frame(&mut reader).await {
+        let Envelope { id, request } = match frame.decode(session.encoding) {
+            Ok(r) => r,
+            Err(err) => {
+                session.send(frame.request_id(session.encoding), Response::Error { message: err.to_string() }).await;
+                continue;
+            }
+        };
+
+        let Ok(permit) = in_flight.clone().acquire_owned().await else {
+            break;
         };
 
-        if len > MAX_PACKET {
+        // Requests are answered as they finish, so a slow one does not hold
+        // up the rest; the id tells the client which is which.
+        let (daemon, session) = (daemon.clone(), session.clone());
+        tokio::spawn(async move {
+            let response = daemon.dispatch(id, request, &session).await;
+            session.send(id, response).await;
+            drop(permit);
+        });
+    }
+
+    // Requests still running hold the session until they have answered. The
+    // engine callback only keeps a weak sender, so the writer ends after that.
+    drop(session);
+    let _ = writer_task.await;
+}
+
+pub async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R) -> Option<Frame> {
+    match lunio_protocol::read_frame(reader, MAX_PACKET).await {
+        Ok(frame) => Some(frame),
+        Err(CodecError::TooLarge(len)) => {
             eprintln!("Packet too large: {}", len);
-            return;
+            None
         }
+        Err(_) => None
+    }
+}
 
-        let mut buf = vec![0u8; len];
-
-        if socket.read_exact(&mut buf).await.is_err() {
+async fn write_frames<S: AsyncWrite>(mut writer: WriteHalf<S>, mut frames: Receiver<Frame>) {
+    while let Some(frame) = frames.recv().await {
+        if write_frame(&mut writer, &frame).await.is_err() {
             return;
         }
+    }
+}
 
-        let request: Request = match serde_json::from_slice(&buf) {
-            Ok(r) => r,
-            Err(err) => {
-                let resp = Response::Error { message: err.to_string() };
-                let out = serde_json::to_vec(&resp).unwrap();
-                let _ = socket.write_u32(out.len() as u32).await;
-                let _ = socket.write_all(&out).await;
+pub async fn start_server(daemon: Daemon, options: &ServerOptions) -> anyhow::Result<()> {
+    let tcp = async {
+        match &options.tcp {
+            Some(addr) => serve_tcp(daemon.clone(), addr).await,
+            None => std::future::pending().await
+        }
+    };
 
-                continue;
-            }
-        };
+    #[cfg(unix)]
+    {
+        let unix = serve_unix(daemon.clone(), &options.socket);
+        tokio::select! {
+            res = tcp => res,
+            res = unix => res
+        }
+    }
 
-        let response = daemon.dispatch(request).await;
-        let out = serde_json::to_vec(&response).unwrap();
+    #[cfg(not(unix))]
+    tcp.await
+}
 
-        let _ = socket.write_u32(out.len() as u32).await;
-        let _ = socket.write_all(&out).await;
-    }
+/// Any local user can reach a TCP port, so every connection has to prove it
+/// can read the token file first.
+async fn serve_tcp(daemon: Daemon, addr: &str) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    let token = Arc::new(Token::create(&paths::token_path())?);
+    println!("[lunio-daemon] listening on {addr}");
 
+    loop {
+        let (socket, _) = listener.accept().await?;
+        tokio::spawn(handle_connection(daemon.clone(), socket, Some(token.clone())));
+    }
 }
 
-pub async fn start_server(daemon: Daemon) -> anyhow::Result<()> {
-    println!("[lunio-daemon] running on localhost:9000");
-    let listener = TcpListener::bind("localhost:9000").await?;
+#[cfg(unix)]
+async fn serve_unix(daemon: Daemon, path: &Path) -> anyhow::Result<()> {
+    let listener = bind_unix(path).await?;
+    let _guard = SocketFile(path.to_path_buf());
+    println!("[lunio-daemon] listening on {}", path.display());
 
     loop {
         let (socket, _) = listener.accept().await?;
-        let daemon_clone = daemon.clone();
+        tokio::spawn(handle_connection(daemon.clone(), socket, None));
+    }
+}
 
-        tokio::spawn(async move {
-            handle_connection(daemon_clone, socket).await;
-        });
+/// Binds `path` so that only the current user can connect. A socket left
+/// behind by a daemon that died is replaced; a live one is an error.
+#[cfg(unix)]
+async fn bind_unix(path: &Path) -> anyhow::Result<UnixListener> {
+    if let Some(dir) = path.parent() {
+        create_private_dir(dir)?;
+    }
+
+    if UnixStream::connect(path).await.is_ok() {
+        anyhow::bail!("another daemon is already listening on {}", path.display());
     }
-}
\ No newline at end of file
+
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+        _ => {}
+    }
+
+    let listener = UnixListener::bind(path)?;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+
+    Ok(listener)
+}
+
+/// Removes the socket file once the server stops, including when it is
+/// dropped on shutdown.
+#[cfg(unix)]
+struct SocketFile(PathBuf);
+
+#[cfg(unix)]
+impl Drop for SocketFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
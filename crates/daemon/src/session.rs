@@ -0,0 +1,127 @@
+use std::{collections::VecDeque, path::PathBuf, sync::{Arc, Mutex, RwLock, atomic::{AtomicBool, Ordering}}};
+
+use lunio_core::{EngineRuntime, engine::listing::ListOptions, models::FileMeta};
+use tokio::sync::mpsc::{Sender, error::TrySendError};
+
+use crate::{handshake::Negotiated, protocol::{Encoding, Frame, Reply, Response, event}};
+
+/// Frames a connection may have waiting for the socket. Past that, responses
+/// wait for room and pushed events are dropped, so a client that reads
+/// slowly cannot make the daemon buffer without end.
+pub const FRAME_QUEUE: usize = 64;
+
+/// Listings a connection keeps for the pages after the first, so views that
+/// page through different folders at once do not push each other out.
+pub const CACHED_LISTINGS: usize = 8;
+
+/// What a connection keeps between requests. Everything after the handshake
+/// is written through `frames`, so pushed events never interleave with a
+/// response.
+pub struct Session {
+    frames: Sender<Frame>,
+    pub negotiated: Negotiated,
+    pub encoding: Encoding,
+    subscriptions: Arc<RwLock<Vec<PathBuf>>>,
+    listening: AtomicBool,
+    listings: Mutex<VecDeque<Listing>>
+}
+
+/// A directory as listed, filtered and sorted, for the pages after the first.
+struct Listing {
+    path: String,
+    options: ListOptions,
+    entries: Arc<[FileMeta]>
+}
+
+impl Session {
+    pub fn new(frames: Sender<Frame>, negotiated: Negotiated) -> Self {
+        Self {
+            frames,
+            encoding: Encoding::negotiated(&negotiated.capabilities),
+            negotiated,
+            subscriptions: Default::default(),
+            listening: AtomicBool::new(false),
+            listings: Mutex::new(VecDeque::with_capacity(CACHED_LISTINGS))
+        }
+    }
+
+    /// The listing a later page of `path` is cut from. Asking for the first
+    /// page finds none, so the directory is read again; later pages reuse
+    /// that listing, so they line up and the directory is not re-sorted per
+    /// page.
+    pub fn cached_listing(&self, path: &str, options: &ListOptions, offset: usize) -> Option<Arc<[FileMeta]>> {
+        if offset == 0 {
+            return None;
+        }
+
+        let mut listings = self.listings.lock().unwrap();
+        let at = listings.iter().position(|l| l.path == path && l.options == *options)?;
+        let listing = listings.remove(at)?;
+        let entries = listing.entries.clone();
+        listings.push_front(listing);
+        Some(entries)
+    }
+
+    /// Keeps `entries` for later pages, forgetting the least recently used
+    /// listing when there are too many.
+    pub fn remember_listing(&self, path: &str, options: &ListOptions, entries: Vec<FileMeta>) -> Arc<[FileMeta]> {
+        let entries: Arc<[FileMeta]> = entries.into();
+
+        let mut listings = self.listings.lock().unwrap();
+        listings.retain(|l| l.path != path || l.options != *options);
+        listings.push_front(Listing { path: path.to_string(), options: options.clone(), entries: entries.clone() });
+        listings.truncate(CACHED_LISTINGS);
+
+        entries
+    }
+
+    /// Queues the response to request `id`, waiting while the queue is full.
+    pub async fn send(&self, id: Option<u64>, response: Response) -> bool {
+        let frame = Frame::reply(Reply { id, response }, self.encoding).unwrap_or_else(|err| {
+            Frame::encode(&Reply { id, response: Response::Error { message: err.to_string() } }, self.encoding).unwrap()
+        });
+        self.frames.send(frame).await.is_ok()
+    }
+
+    /// Replaces the watched paths. The engine callback is registered on the
+    /// first call and drops itself once the connection is gone.
+    pub fn subscribe(&self, engine: &EngineRuntime, paths: Vec<PathBuf>) {
+        *self.subscriptions.write().unwrap() = paths;
+
+        if self.listening.swap(true, Ordering::Relaxed) {
+            return;
+        }
+
+        let frames = self.frames.downgrade();
+        let subscriptions = self.subscriptions.clone();
+        let encoding = self.encoding;
+
+        engine.subscribe(Box::new(move |change| {
+            let Some(frames) = frames.upgrade() else {
+                return false;
+            };
+
+            // A subscriber also hears about the paths it sits below, so a
+            // watched folder that gets moved or deleted is noticed.
+            let wanted = subscriptions.read().unwrap().iter().any(|sub| {
+                change.paths().iter().any(|p| p.starts_with(sub) || sub.starts_with(p))
+            });
+
+            if !wanted {
+                return true;
+            }
+
+            // Engine threads cannot wait on a slow client; it misses events
+            // until it catches up instead.
+            let reply = Reply { id: None, response: Response::Event { event: event(change.clone()) } };
+            let frame = match Frame::encode(&reply, encoding) {
+                Ok(frame) => frame,
+                Err(e) => {
+                    eprintln!("[lunio-daemon] dropping an event that failed to encode: {e}");
+                    return true;
+                }
+            };
+            !matches!(frames.try_send(frame), Err(TrySendError::Closed(_)))
+        }));
+    }
+}
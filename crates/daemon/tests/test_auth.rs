@@ -0,0 +1,75 @@
+#![cfg(unix)]
+
+use std::{fs, os::unix::fs::PermissionsExt, path::PathBuf, sync::Arc};
+
+use lunio_core::EngineRuntime;
+use lunio_daemon::{auth::Token, daemon::Daemon, handshake::HANDSHAKE_TIMEOUT, protocol::{Handshake, read_frame}, server::handle_connection};
+use sha2::{Digest, Sha256};
+
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("lunio-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+fn prove(challenge: &str, secret: &str) -> String {
+    format!("{:x}", Sha256::digest(format!("{challenge}:{secret}")))
+}
+
+#[test]
+fn tokens_only_accept_a_full_proof() {
+    let dir = scratch("token");
+    let path = dir.join("run/daemon.token");
+    let token = Token::create(&path).unwrap();
+
+    let secret = fs::read_to_string(&path).unwrap();
+    assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+    assert_eq!(fs::metadata(path.parent().unwrap()).unwrap().permissions().mode() & 0o777, 0o700);
+
+    let proof = prove("abc", &secret);
+    assert!(token.verify("abc", &proof));
+    assert!(!token.verify("abd", &proof));
+    assert!(!token.verify("abc", &prove("abc", "guess")));
+    assert!(!token.verify("abc", &proof[..proof.len() - 1]));
+    assert!(!token.verify("abc", ""));
+
+    drop(token);
+    assert!(!path.exists());
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn tokens_refuse_a_directory_others_could_reach() {
+    let dir = scratch("token-dir");
+    let run = dir.join("run");
+    fs::create_dir_all(&run).unwrap();
+    fs::set_permissions(&run, fs::Permissions::from_mode(0o755)).unwrap();
+
+    assert!(Token::create(&run.join("daemon.token")).is_err());
+    assert!(!run.join("daemon.token").exists());
+
+    fs::set_permissions(&run, fs::Permissions::from_mode(0o700)).unwrap();
+    assert!(Token::create(&run.join("daemon.token")).is_ok());
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[tokio::test(start_paused = true)]
+async fn silent_clients_are_dropped() {
+    let dir = scratch("silent");
+    let daemon = Daemon::new(EngineRuntime::new(dir.join(".cache"), None, None));
+    let token = Arc::new(Token::create(&dir.join("run/daemon.token")).unwrap());
+
+    let (mut client, server) = tokio::io::duplex(4096);
+    let served = tokio::spawn(handle_connection(daemon, server, Some(token)));
+
+    let greeting: Handshake = read_frame(&mut client, 4096).await.unwrap().parse().unwrap();
+    assert!(greeting.challenge.is_some());
+
+    // Time is paused, so this returns as soon as the daemon gives up.
+    let started = tokio::time::Instant::now();
+    assert!(read_frame(&mut client, 4096).await.is_err());
+    assert!(started.elapsed() >= HANDSHAKE_TIMEOUT);
+    served.await.unwrap();
+
+    let _ = fs::remove_dir_all(&dir);
+}
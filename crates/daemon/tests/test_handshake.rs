@@ -0,0 +1,98 @@
+#![cfg(unix)]
+
+use std::{fs, path::PathBuf};
+
+use lunio_daemon::{auth::Token, handshake::{Negotiated, negotiate}, protocol::{CAP_BINARY, CAP_EVENTS, ClientHello, Frame, Handshake, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION, Response, ResponseData, read_frame, write_frame}};
+use sha2::{Digest, Sha256};
+use tokio::io::{duplex, split};
+
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("lunio-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+/// Runs `negotiate` against a client that answers the greeting with `answer`,
+/// returning what the daemon settled on and what it told the client.
+async fn greet(token: Option<&Token>, answer: impl FnOnce(Handshake) -> ClientHello) -> (Option<Negotiated>, Response) {
+    let (mut client, server) = duplex(4096);
+    let (mut reader, mut writer) = split(server);
+
+    let talking = async {
+        let greeting: Handshake = read_frame(&mut client, 4096).await.unwrap().parse().unwrap();
+        write_frame(&mut client, &Frame::json(&answer(greeting)).unwrap()).await.unwrap();
+        read_frame(&mut client, 4096).await.unwrap().parse::<Response>().unwrap()
+    };
+    tokio::join!(negotiate(&mut reader, &mut writer, token), talking)
+}
+
+fn hello(min_protocol: u8, max_protocol: u8, capabilities: &[&str], proof: Option<String>) -> ClientHello {
+    ClientHello::Hello {
+        min_protocol,
+        max_protocol,
+        capabilities: capabilities.iter().map(|c| c.to_string()).collect(),
+        proof
+    }
+}
+
+fn refusal(response: Response) -> String {
+    match response {
+        Response::Error { message } => message,
+        other => panic!("expected a refusal, got {other:?}")
+    }
+}
+
+#[tokio::test]
+async fn versions_must_overlap() {
+    let (negotiated, response) = greet(None, |_| hello(PROTOCOL_VERSION + 1, PROTOCOL_VERSION + 2, &[CAP_BINARY], None)).await;
+    assert!(negotiated.is_none());
+    assert!(refusal(response).contains("incompatible protocol"));
+
+    let (negotiated, _) = greet(None, |_| hello(MIN_PROTOCOL_VERSION, PROTOCOL_VERSION + 5, &[CAP_BINARY], None)).await;
+    assert_eq!(negotiated.unwrap().protocol, PROTOCOL_VERSION);
+}
+
+#[tokio::test]
+async fn capabilities_are_what_both_sides_support() {
+    let (negotiated, response) = greet(None, |_| hello(MIN_PROTOCOL_VERSION, PROTOCOL_VERSION, &[CAP_EVENTS, "teleport", CAP_BINARY], None)).await;
+
+    let negotiated = negotiated.unwrap();
+    assert_eq!(negotiated.capabilities, [CAP_EVENTS, CAP_BINARY]);
+    match response {
+        Response::Ok { data: Some(ResponseData::Welcome { protocol, capabilities }) } => {
+            assert_eq!(protocol, PROTOCOL_VERSION);
+            assert_eq!(capabilities, negotiated.capabilities);
+        }
+        other => panic!("expected a welcome, got {other:?}")
+    }
+
+    let (negotiated, response) = greet(None, |_| hello(MIN_PROTOCOL_VERSION, PROTOCOL_VERSION, &[CAP_EVENTS], None)).await;
+    assert!(negotiated.is_none());
+    assert!(refusal(response).contains(CAP_BINARY));
+}
+
+#[tokio::test]
+async fn clients_must_prove_they_hold_the_token() {
+    let dir = scratch("handshake");
+    let path = dir.join("run/daemon.token");
+    let token = Token::create(&path).unwrap();
+    let secret = fs::read_to_string(&path).unwrap();
+    let prove = |challenge: &str, secret: &str| format!("{:x}", Sha256::digest(format!("{challenge}:{secret}")));
+
+    let (negotiated, response) = greet(Some(&token), |g| {
+        hello(MIN_PROTOCOL_VERSION, PROTOCOL_VERSION, &[CAP_BINARY], Some(prove(&g.challenge.unwrap(), "guess")))
+    }).await;
+    assert!(negotiated.is_none());
+    assert_eq!(refusal(response), "authentication failed");
+
+    let (negotiated, _) = greet(Some(&token), |_| hello(MIN_PROTOCOL_VERSION, PROTOCOL_VERSION, &[CAP_BINARY], None)).await;
+    assert!(negotiated.is_none());
+
+    let (negotiated, _) = greet(Some(&token), |g| {
+        hello(MIN_PROTOCOL_VERSION, PROTOCOL_VERSION, &[CAP_BINARY], Some(prove(&g.challenge.unwrap(), &secret)))
+    }).await;
+    assert!(negotiated.is_some());
+
+    drop(token);
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,206 @@
+#![cfg(unix)]
+
+use std::{fs, path::{Path, PathBuf}};
+
+use lunio_client::{Client, FileEntry, ListOptions, SearchMode, SortDirection};
+use lunio_core::EngineRuntime;
+use lunio_daemon::{
+    commands::list_dir::CHUNK_SIZE,
+    daemon::Daemon,
+    protocol::{CAPABILITIES, ClientHello, Envelope, Frame, Handshake, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION, Reply, Request, Response, ResponseData, read_frame, write_frame},
+    server::{MAX_IN_FLIGHT, handle_connection}
+};
+use tokio::net::UnixListener;
+
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("lunio-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("files")).unwrap();
+    dir
+}
+
+/// A daemon with `dir/files` indexed, serving a socket in `dir`, and a
+/// client connected to it.
+async fn serve(dir: &Path) -> Client {
+    let engine = EngineRuntime::new(dir.join(".cache"), None, None);
+    engine.full_scan(dir.join("files"));
+    let daemon = Daemon::new(engine);
+
+    let socket = dir.join("daemon.sock");
+    let listener = UnixListener::bind(&socket).unwrap();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(handle_connection(daemon.clone(), stream, None));
+        }
+    });
+
+    Client::connect_unix(&socket).await.unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn concurrent_requests_get_their_own_answers() {
+    let dir = scratch("concurrent");
+    let files = dir.join("files");
+    for i in 0..8 {
+        fs::write(files.join(format!("file-{i}.txt")), b"").unwrap();
+    }
+    let client = serve(&dir).await;
+
+    // More than may run at once, so some have to wait for a free slot.
+    let asked: Vec<_> = (0..MAX_IN_FLIGHT * 3)
+        .map(|n| {
+            let (client, files) = (client.clone(), files.clone());
+            tokio::spawn(async move {
+                let names: Vec<String> = match n % 2 {
+                    0 => client.search(format!("file-{}", n % 8), None, SearchMode::Substring).await.unwrap(),
+                    _ => client.list_dir(files.to_string_lossy()).await.unwrap()
+                }
+                .into_iter()
+                .map(|e| e.path.rsplit('/').next().unwrap().to_string())
+                .collect();
+
+                (n, names)
+            })
+        })
+        .collect();
+
+    for asking in asked {
+        let (n, names) = asking.await.unwrap();
+        match n % 2 {
+            0 => assert_eq!(names, vec![format!("file-{}.txt", n % 8)]),
+            _ => assert_eq!(names.len(), 8)
+        }
+    }
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+fn names(entries: &[FileEntry]) -> Vec<String> {
+    entries.iter().map(|e| e.path.rsplit('/').next().unwrap().to_string()).collect()
+}
+
+#[tokio::test]
+async fn pages_are_windows_of_one_order() {
+    let dir = scratch("pages");
+    let files = dir.join("files");
+    for i in 0..10 {
+        fs::write(files.join(format!("file-{i}.txt")), b"").unwrap();
+    }
+    let client = serve(&dir).await;
+    let path = files.to_string_lossy().to_string();
+
+    let first = client.list_dir_page(&path, 0, Some(4), ListOptions::default()).await.unwrap();
+    assert_eq!(first.total, 10);
+    assert_eq!(names(&first.entries), ["file-0.txt", "file-1.txt", "file-2.txt", "file-3.txt"]);
+
+    let middle = client.list_dir_page(&path, 4, Some(4), ListOptions::default()).await.unwrap();
+    assert_eq!(names(&middle.entries), ["file-4.txt", "file-5.txt", "file-6.txt", "file-7.txt"]);
+
+    let last = client.list_dir_page(&path, 8, Some(4), ListOptions::default()).await.unwrap();
+    assert_eq!(names(&last.entries), ["file-8.txt", "file-9.txt"]);
+
+    let past = client.list_dir_page(&path, 40, Some(4), ListOptions::default()).await.unwrap();
+    assert!(past.entries.is_empty());
+    assert_eq!(past.total, 10);
+
+    let reversed = ListOptions { direction: SortDirection::Descending, ..ListOptions::default() };
+    let flipped = client.list_dir_page(&path, 1, Some(2), reversed).await.unwrap();
+    assert_eq!(names(&flipped.entries), ["file-8.txt", "file-7.txt"]);
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn match_ranges_count_utf16_units() {
+    let dir = scratch("utf16-matches");
+    fs::write(dir.join("files/\u{1F600}report.txt"), b"").unwrap();
+    let client = serve(&dir).await;
+
+    let hits = client.search("rpt", None, SearchMode::Fuzzy).await.unwrap();
+    assert_eq!(hits.len(), 1);
+
+    let name: Vec<u16> = "\u{1F600}report.txt".encode_utf16().collect();
+    let matched: String = hits[0].matches.iter().map(|&(start, end)| String::from_utf16(&name[start..end]).unwrap()).collect();
+    assert_eq!(matched, "rpt");
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn folders_paged_side_by_side_keep_their_own_order() {
+    let dir = scratch("side-by-side");
+    let files = dir.join("files");
+    for folder in ["left", "right"] {
+        fs::create_dir_all(files.join(folder)).unwrap();
+        for i in 0..4 {
+            fs::write(files.join(format!("{folder}/{i}.txt")), b"").unwrap();
+        }
+    }
+    let client = serve(&dir).await;
+    let (left, right) = (files.join("left").to_string_lossy().to_string(), files.join("right").to_string_lossy().to_string());
+
+    let first = client.list_dir_page(&left, 0, Some(2), ListOptions::default()).await.unwrap();
+    assert_eq!(names(&first.entries), ["0.txt", "1.txt"]);
+    client.list_dir_page(&right, 0, Some(2), ListOptions::default()).await.unwrap();
+
+    // A file that arrives between pages waits for the next first page.
+    fs::write(files.join("left/00.txt"), b"").unwrap();
+    let job = client.scan(&left).await.unwrap();
+    while client.scan_status(Some(job.id)).await.unwrap()[0].state == "running" {
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+
+    let second = client.list_dir_page(&left, 2, Some(2), ListOptions::default()).await.unwrap();
+    assert_eq!(second.total, 4);
+    assert_eq!(names(&second.entries), ["2.txt", "3.txt"]);
+
+    let again = client.list_dir_page(&left, 0, Some(2), ListOptions::default()).await.unwrap();
+    assert_eq!(again.total, 5);
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn streamed_listings_start_then_chunk_then_end() {
+    let dir = scratch("stream");
+    let files = dir.join("files");
+    for i in 0..CHUNK_SIZE + 5 {
+        fs::write(files.join(format!("file-{i}.txt")), b"").unwrap();
+    }
+    let engine = EngineRuntime::new(dir.join(".cache"), None, None);
+    engine.full_scan(&files);
+
+    let (mut socket, server) = tokio::io::duplex(1 << 16);
+    tokio::spawn(handle_connection(Daemon::new(engine), server, None));
+
+    let _: Handshake = read_frame(&mut socket, 1 << 20).await.unwrap().parse().unwrap();
+    let hello = ClientHello::Hello {
+        min_protocol: MIN_PROTOCOL_VERSION,
+        max_protocol: PROTOCOL_VERSION,
+        capabilities: CAPABILITIES.iter().map(|c| c.to_string()).filter(|c| c != "msgpack").collect(),
+        proof: None
+    };
+    write_frame(&mut socket, &Frame::json(&hello).unwrap()).await.unwrap();
+    let _: Response = read_frame(&mut socket, 1 << 20).await.unwrap().parse().unwrap();
+
+    let request = Envelope {
+        id: Some(7),
+        request: Request::ListDir { path: files.to_string_lossy().to_string(), offset: 0, limit: None, stream: true, options: Default::default() }
+    };
+    write_frame(&mut socket, &Frame::json(&request).unwrap()).await.unwrap();
+
+    let mut seen = Vec::new();
+    loop {
+        let reply: Reply = read_frame(&mut socket, 1 << 24).await.unwrap().parse().unwrap();
+        assert_eq!(reply.id, Some(7));
+        match reply.response {
+            Response::Ok { data: Some(ResponseData::ListingStart { total }) } => seen.push(format!("start {total}")),
+            Response::Ok { data: Some(ResponseData::ListingChunk { entries }) } => seen.push(format!("chunk {}", entries.len())),
+            Response::Ok { data: Some(ResponseData::ListingEnd) } => break,
+            other => panic!("unexpected {other:?}")
+        }
+    }
+    assert_eq!(seen, [format!("start {}", CHUNK_SIZE + 5), format!("chunk {CHUNK_SIZE}"), "chunk 5".to_string()]);
+
+    let _ = fs::remove_dir_all(&dir);
+}
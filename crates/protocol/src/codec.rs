@@ -0,0 +1,185 @@
+use std::io;
+
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{CAP_MSGPACK, messages::{Reply, Response}};
+
+/// Set in a frame's length prefix when raw bytes follow the message. Such a
+/// payload starts with the big-endian length of its encoded part.
+pub const BINARY_FRAME: u32 = 1 << 31;
+
+/// The largest payload a length prefix can describe.
+pub const MAX_FRAME: usize = (BINARY_FRAME - 1) as usize;
+
+/// How messages after the handshake are encoded. The handshake itself is
+/// always JSON, since nothing has been agreed on yet.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Encoding {
+    #[default]
+    Json,
+    /// MessagePack with named fields, as the tagged and flattened types
+    /// need field names to read back.
+    MessagePack
+}
+
+impl Encoding {
+    /// What a connection uses once `capabilities` have been agreed on.
+    pub fn negotiated(capabilities: &[String]) -> Self {
+        match capabilities.iter().any(|c| c == CAP_MSGPACK) {
+            true => Encoding::MessagePack,
+            false => Encoding::Json
+        }
+    }
+
+    pub fn to_vec(self, value: &impl Serialize) -> Result<Vec<u8>, CodecError> {
+        Ok(match self {
+            Encoding::Json => serde_json::to_vec(value)?,
+            Encoding::MessagePack => rmp_serde::to_vec_named(value)?
+        })
+    }
+
+    pub fn from_slice<T: DeserializeOwned>(self, buf: &[u8]) -> Result<T, CodecError> {
+        Ok(match self {
+            Encoding::Json => serde_json::from_slice(buf)?,
+            Encoding::MessagePack => rmp_serde::from_slice(buf)?
+        })
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CodecError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    #[error(transparent)]
+    MessagePackEncode(#[from] rmp_serde::encode::Error),
+
+    #[error(transparent)]
+    MessagePackDecode(#[from] rmp_serde::decode::Error),
+
+    #[error("frame of {0} bytes is over the limit")]
+    TooLarge(usize),
+
+    #[error("truncated binary frame")]
+    Truncated,
+
+    #[error("binary frame without data")]
+    MissingData
+}
+
+/// One length-prefixed message: a big-endian `u32` header, then `payload`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub header: u32,
+    pub payload: Vec<u8>
+}
+
+impl Frame {
+    pub fn encode(value: &impl Serialize, encoding: Encoding) -> Result<Self, CodecError> {
+        let payload = encoding.to_vec(value)?;
+        Ok(Self { header: payload.len() as u32, payload })
+    }
+
+    pub fn json(value: &impl Serialize) -> Result<Self, CodecError> {
+        Self::encode(value, Encoding::Json)
+    }
+
+    pub fn is_binary(&self) -> bool {
+        self.header & BINARY_FRAME != 0
+    }
+
+    /// Reads a frame without attached bytes, such as a request.
+    pub fn decode<T: DeserializeOwned>(&self, encoding: Encoding) -> Result<T, CodecError> {
+        encoding.from_slice(&self.payload)
+    }
+
+    /// Reads a handshake frame.
+    pub fn parse<T: DeserializeOwned>(&self) -> Result<T, CodecError> {
+        self.decode(Encoding::Json)
+    }
+
+    /// Encodes a reply, as a binary frame if it carries bytes.
+    pub fn reply(reply: Reply, encoding: Encoding) -> Result<Self, CodecError> {
+        let Response::Binary { data, bytes } = reply.response else {
+            return Self::encode(&reply, encoding);
+        };
+
+        let head = encoding.to_vec(&Reply { id: reply.id, response: Response::Ok { data: Some(data) } })?;
+        let mut payload = Vec::with_capacity(4 + head.len() + bytes.len());
+        payload.extend_from_slice(&(head.len() as u32).to_be_bytes());
+        payload.extend_from_slice(&head);
+        payload.extend_from_slice(&bytes);
+
+        if payload.len() > MAX_FRAME {
+            return Err(CodecError::TooLarge(payload.len()));
+        }
+
+        Ok(Self { header: payload.len() as u32 | BINARY_FRAME, payload })
+    }
+
+    /// The reverse of [`Frame::reply`]: a binary frame comes back as
+    /// [`Response::Binary`].
+    pub fn into_reply(self, encoding: Encoding) -> Result<Reply, CodecError> {
+        if !self.is_binary() {
+            return self.decode(encoding);
+        }
+
+        let head_end = self.head_end()?;
+        let mut buf = self.payload;
+
+        let bytes = buf.split_off(head_end);
+        let reply: Reply = encoding.from_slice(&buf[4..])?;
+
+        match reply.response {
+            Response::Ok { data: Some(data) } => Ok(Reply { id: reply.id, response: Response::Binary { data, bytes } }),
+            _ => Err(CodecError::MissingData)
+        }
+    }
+
+    /// Just the `request_id` of a message, which can often still be read
+    /// when the rest cannot, so the failure reaches whoever is waiting.
+    pub fn request_id(&self, encoding: Encoding) -> Option<u64> {
+        #[derive(Deserialize)]
+        struct Id {
+            request_id: Option<u64>
+        }
+
+        let head = match self.is_binary() {
+            true => &self.payload[4..self.head_end().ok()?],
+            false => &self.payload[..]
+        };
+
+        encoding.from_slice::<Id>(head).ok()?.request_id
+    }
+
+    /// Where the encoded part of a binary frame ends and its bytes begin.
+    fn head_end(&self) -> Result<usize, CodecError> {
+        self.payload.get(..4)
+            .map(|b| u32::from_be_bytes(b.try_into().unwrap()) as usize + 4)
+            .filter(|&end| end <= self.payload.len())
+            .ok_or(CodecError::Truncated)
+    }
+}
+
+/// Reads the next frame, refusing payloads over `limit` bytes.
+pub async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R, limit: usize) -> Result<Frame, CodecError> {
+    let header = reader.read_u32().await?;
+    let len = (header & !BINARY_FRAME) as usize;
+
+    if len > limit {
+        return Err(CodecError::TooLarge(len));
+    }
+
+    let mut payload = vec![0u8; len];
+    reader.read_exact(&mut payload).await?;
+    Ok(Frame { header, payload })
+}
+
+pub async fn write_frame<W: AsyncWrite + Unpin>(writer: &mut W, frame: &Frame) -> io::Result<()> {
+    writer.write_u32(frame.header).await?;
+    writer.write_all(&frame.payload).await
+}
@@ -0,0 +1,21 @@
+pub mod codec;
+pub mod messages;
+pub mod paths;
+
+pub use codec::{BINARY_FRAME, CodecError, Encoding, Frame, MAX_FRAME, read_frame, write_frame};
+pub use messages::*;
+
+/// Newest and oldest protocol spoken on either side. Version 2 added the
+/// client's hello; nothing older can be served.
+pub const PROTOCOL_VERSION: u8 = 2;
+pub const MIN_PROTOCOL_VERSION: u8 = 2;
+
+/// Optional features a connection may use once both sides have named them.
+pub const CAPABILITIES: &[&str] = &[CAP_BINARY, CAP_EVENTS, CAP_MSGPACK];
+pub const CAP_BINARY: &str = "binary";
+pub const CAP_EVENTS: &str = "events";
+/// Everything after the handshake is MessagePack rather than JSON.
+pub const CAP_MSGPACK: &str = "msgpack";
+
+/// Where a daemon started with `--tcp` listens by default.
+pub const DEFAULT_TCP_ADDR: &str = "localhost:9000";
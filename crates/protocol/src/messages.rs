@@ -0,0 +1,327 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Request {
+    Scan { root: String },
+    ScanStatus { id: Option<u64> },
+    CancelScan { id: u64 },
+    Search {
+        query: String,
+        limit: Option<usize>,
+        #[serde(default)]
+        mode: SearchMode
+    },
+    /// Lists a directory, or the `offset`/`limit` window of it. With
+    /// `stream` set the entries come as `ListingStart`, any number of
+    /// `ListingChunk`s and a closing `ListingEnd`, all with this request's id.
+    ListDir {
+        path: String,
+        #[serde(default)]
+        offset: usize,
+        #[serde(default)]
+        limit: Option<usize>,
+        #[serde(default)]
+        stream: bool,
+        #[serde(flatten)]
+        options: ListOptions
+    },
+
+    RequestThumbnail { id: String },
+    GetThumbnail { id: String },
+    RequestThumbnails {
+        ids: Vec<String>,
+        #[serde(default)]
+        priority: ThumbnailPriority
+    },
+    GetThumbnails { ids: Vec<String> },
+
+    OpenFile { path: String },
+
+    AddRoot {
+        path: String,
+        #[serde(flatten)]
+        options: RootOptions
+    },
+    RemoveRoot { path: String },
+    ListRoots,
+
+    /// Replaces the paths this connection gets events for; an empty list
+    /// unsubscribes.
+    Subscribe { paths: Vec<String> },
+
+    Shutdown
+}
+
+/// A request on the wire. Its `request_id` is echoed on the response, which
+/// lets a client keep several requests in flight on one connection.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Envelope {
+    #[serde(rename = "request_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<u64>,
+    #[serde(flatten)]
+    pub request: Request
+}
+
+/// A response with the id of the request it answers; pushed events have none.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Reply {
+    #[serde(rename = "request_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<u64>,
+    #[serde(flatten)]
+    pub response: Response
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status")]
+pub enum Response {
+    #[serde(rename = "ok")]
+    Ok { data: Option<ResponseData> },
+
+    #[serde(rename = "error")]
+    Error { message: String },
+
+    /// Sent as a binary frame: `data` as an `ok` response, then `bytes`.
+    #[serde(skip)]
+    Binary { data: ResponseData, bytes: Vec<u8> },
+
+    /// Pushed by the daemon for subscribed paths, never sent in reply.
+    #[serde(rename = "event")]
+    Event { event: Event }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ResponseData {
+    SearchResults { entries: Vec<FileEntry> },
+    /// `total` counts the whole directory, not just this page.
+    DirectoryListing {
+        entries: Vec<FileEntry>,
+        #[serde(default)]
+        total: usize
+    },
+    ListingStart { total: usize },
+    ListingChunk { entries: Vec<FileEntry> },
+    ListingEnd,
+    Roots { roots: Vec<RootEntry> },
+    ScanJobs { jobs: Vec<ScanJob> },
+    /// What the handshake settled on.
+    Welcome { protocol: u8, capabilities: Vec<String> },
+    /// The thumbnail's bytes follow in the same binary frame.
+    Thumbnail { id: String },
+    /// The `ready` thumbnails' bytes follow back to back, in order.
+    Thumbnails {
+        ready: Vec<ThumbnailSlice>,
+        pending: Vec<String>,
+        missing: Vec<String>
+    },
+    Ack
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchMode {
+    #[default]
+    Substring,
+    Fuzzy,
+    Query
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThumbnailPriority {
+    Low,
+    #[default]
+    Normal,
+    High
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WatchMode {
+    #[default]
+    Auto,
+    Native,
+    Poll
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortKey {
+    #[default]
+    Name,
+    Size,
+    Modified,
+    Created,
+    Extension,
+    Kind
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortDirection {
+    #[default]
+    Ascending,
+    Descending
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NameOrder {
+    /// Byte-wise and case-sensitive.
+    #[default]
+    Ordinal,
+    CaseInsensitive,
+    /// Case-insensitive, with runs of digits compared by value.
+    Natural
+}
+
+/// How a directory listing is sorted and filtered. Sorting happens in the
+/// daemon, so every page of a listing comes from the same order.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ListOptions {
+    pub sort: SortKey,
+    pub direction: SortDirection,
+    pub name_order: NameOrder,
+    pub folders_first: bool,
+    pub include_hidden: bool,
+    /// Only files with one of these extensions; directories are kept.
+    pub extensions: Vec<String>
+}
+
+impl Default for ListOptions {
+    fn default() -> Self {
+        Self {
+            sort: SortKey::Name,
+            direction: SortDirection::Ascending,
+            name_order: NameOrder::Ordinal,
+            folders_first: true,
+            include_hidden: true,
+            extensions: Vec::new()
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileEntry {
+    pub id: String,
+    pub path: String,
+    pub size: u64,
+    pub is_dir: bool,
+    /// `file`, `directory`, `symlink` or `other`.
+    #[serde(default)]
+    pub kind: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link_target: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub link_broken: bool,
+    pub modified: Option<i64>,
+    pub has_thumbnail: bool,
+
+    /// Only set on search results.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<i64>,
+    /// Half-open ranges of the file name that matched, in UTF-16 code units.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub matches: Vec<(usize, usize)>
+}
+
+/// Per-root settings a client may override when adding a root; anything left
+/// out keeps the engine default.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RootOptions {
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    pub max_depth: Option<usize>,
+    pub watch: Option<bool>,
+    pub include_hidden: Option<bool>,
+    pub follow_links: Option<bool>,
+    pub watch_mode: Option<WatchMode>,
+    pub poll_interval_secs: Option<u64>
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RootEntry {
+    pub path: String,
+    pub exclude: Vec<String>,
+    pub max_depth: Option<usize>,
+    pub watch: bool,
+    #[serde(default)]
+    pub include_hidden: bool,
+    #[serde(default)]
+    pub follow_links: bool,
+    #[serde(default)]
+    pub watch_mode: WatchMode,
+    #[serde(default)]
+    pub poll_interval_secs: u64,
+    pub watching: bool,
+    #[serde(default)]
+    pub polling: bool,
+    /// `pending`, `scanning`, `ready` or `failed`.
+    pub status: String,
+    pub error: Option<String>,
+    pub entries: usize,
+    pub last_scan: Option<i64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScanJob {
+    pub id: u64,
+    pub root: String,
+    /// `running`, `completed`, `cancelled` or `failed`.
+    pub state: String,
+    pub dirs: usize,
+    pub files: usize,
+    pub errors: usize,
+    pub elapsed_ms: u64
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ThumbnailSlice {
+    pub id: String,
+    pub len: usize
+}
+
+/// Pushed for paths a connection subscribed to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Event {
+    Created { entry: FileEntry },
+    Modified { entry: FileEntry },
+    Deleted { id: String, path: String },
+    Renamed { id: String, from: String, to: String },
+    ThumbnailReady { id: String, path: String }
+}
+
+/// The daemon's greeting, the first frame on every connection.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Handshake {
+    pub protocol: u8,
+    #[serde(default)]
+    pub min_protocol: u8,
+    pub engine: String,
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+    /// Set on connections that must prove they know the daemon's token
+    /// before sending requests.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub challenge: Option<String>
+}
+
+/// The client's answer to [`Handshake`] and the first frame it sends.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ClientHello {
+    Hello {
+        min_protocol: u8,
+        max_protocol: u8,
+        #[serde(default)]
+        capabilities: Vec<String>,
+        /// Hex SHA-256 of `"{challenge}:{token}"` when challenged.
+        #[serde(default)]
+        proof: Option<String>
+    }
+}
@@ -0,0 +1,44 @@
+use std::path::PathBuf;
+
+/// Per-user directory for the daemon's socket and token: `lunio` in
+/// `$XDG_RUNTIME_DIR`, or `lunio-{uid}` in the temp dir when there is no
+/// runtime dir. The latter is a name anyone can take first, so the daemon
+/// checks who owns it before using it.
+#[cfg(unix)]
+pub fn runtime_dir() -> PathBuf {
+    match dirs::runtime_dir() {
+        Some(dir) => dir.join("lunio"),
+        None => std::env::temp_dir().join(format!("lunio-{}", uid()))
+    }
+}
+
+#[cfg(unix)]
+pub fn uid() -> u32 {
+    // SAFETY: getuid cannot fail and touches no memory of ours.
+    unsafe { libc::getuid() }
+}
+
+#[cfg(not(unix))]
+pub fn runtime_dir() -> PathBuf {
+    dirs::data_local_dir().unwrap_or_else(std::env::temp_dir).join("Lunio")
+}
+
+/// Per-user directory for the daemon's index and thumbnails, so every
+/// daemon the user starts finds the same ones wherever it was started from.
+pub fn cache_dir() -> PathBuf {
+    dirs::cache_dir().unwrap_or_else(std::env::temp_dir).join("Lunio")
+}
+
+/// `$LUNIO_SOCKET` if set, else `daemon.sock` in the runtime dir.
+#[cfg(unix)]
+pub fn socket_path() -> PathBuf {
+    match std::env::var_os("LUNIO_SOCKET") {
+        Some(path) => path.into(),
+        None => runtime_dir().join("daemon.sock")
+    }
+}
+
+/// The file a daemon listening on TCP keeps its token in.
+pub fn token_path() -> PathBuf {
+    runtime_dir().join("daemon.token")
+}
@@ -0,0 +1,318 @@
+use lunio_protocol::{BINARY_FRAME, CAP_MSGPACK, ClientHello, CodecError, Encoding, Envelope, Event, FileEntry, Frame, Handshake, ListOptions, NameOrder, Reply, Request, Response, ResponseData, RootOptions, SearchMode, SortDirection, SortKey, ThumbnailPriority, ThumbnailSlice, WatchMode, read_frame, write_frame};
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::{Value, json};
+
+/// Checks that `value` is written as `expected` and reads back unchanged.
+/// A failure here means old clients or daemons would stop understanding
+/// the new build.
+fn assert_wire<T>(value: T, expected: Value)
+where
+    T: Serialize + DeserializeOwned + PartialEq + std::fmt::Debug
+{
+    assert_eq!(serde_json::to_value(&value).unwrap(), expected);
+    assert_eq!(serde_json::from_value::<T>(expected).unwrap(), value);
+}
+
+fn entry(path: &str) -> FileEntry {
+    FileEntry {
+        id: format!("{:032x}", 7),
+        path: path.into(),
+        size: 12,
+        is_dir: false,
+        kind: "file".into(),
+        link_target: None,
+        link_broken: false,
+        modified: Some(1_700_000_000),
+        has_thumbnail: true,
+        score: None,
+        matches: Vec::new()
+    }
+}
+
+#[test]
+fn requests_keep_their_wire_format() {
+    assert_wire(
+        Envelope {
+            id: Some(3),
+            request: Request::Search { query: "report".into(), limit: Some(5), mode: SearchMode::Fuzzy }
+        },
+        json!({ "request_id": 3, "type": "Search", "query": "report", "limit": 5, "mode": "fuzzy" })
+    );
+
+    assert_wire(
+        Envelope {
+            id: Some(4),
+            request: Request::RequestThumbnails { ids: vec!["a".into()], priority: ThumbnailPriority::High }
+        },
+        json!({ "request_id": 4, "type": "RequestThumbnails", "ids": ["a"], "priority": "high" })
+    );
+
+    assert_wire(
+        Envelope {
+            id: Some(5),
+            request: Request::AddRoot {
+                path: "/media".into(),
+                options: RootOptions { watch_mode: Some(WatchMode::Poll), ..Default::default() }
+            }
+        },
+        json!({
+            "request_id": 5, "type": "AddRoot", "path": "/media",
+            "exclude": [], "max_depth": null, "watch": null, "include_hidden": null,
+            "follow_links": null, "watch_mode": "poll", "poll_interval_secs": null
+        })
+    );
+
+    assert_wire(Envelope { id: None, request: Request::ListRoots }, json!({ "type": "ListRoots" }));
+
+    assert_wire(
+        Envelope {
+            id: Some(6),
+            request: Request::ListDir {
+                path: "/big".into(),
+                offset: 500,
+                limit: Some(100),
+                stream: true,
+                options: ListOptions {
+                    sort: SortKey::Size,
+                    direction: SortDirection::Descending,
+                    name_order: NameOrder::Natural,
+                    include_hidden: false,
+                    extensions: vec!["jpg".into()],
+                    ..Default::default()
+                }
+            }
+        },
+        json!({
+            "request_id": 6, "type": "ListDir", "path": "/big", "offset": 500, "limit": 100, "stream": true,
+            "sort": "size", "direction": "descending", "name_order": "natural",
+            "folders_first": true, "include_hidden": false, "extensions": ["jpg"]
+        })
+    );
+
+    // Fields added after the first release must stay optional.
+    let bare: Envelope = serde_json::from_value(json!({ "type": "Search", "query": "x", "limit": null })).unwrap();
+    assert_eq!(bare.request, Request::Search { query: "x".into(), limit: None, mode: SearchMode::Substring });
+    let bare: Request = serde_json::from_value(json!({ "type": "AddRoot", "path": "/x" })).unwrap();
+    assert_eq!(bare, Request::AddRoot { path: "/x".into(), options: RootOptions::default() });
+    let bare: Request = serde_json::from_value(json!({ "type": "ListDir", "path": "/x" })).unwrap();
+    assert_eq!(bare, Request::ListDir { path: "/x".into(), offset: 0, limit: None, stream: false, options: ListOptions::default() });
+    let bare: Request = serde_json::from_value(json!({ "type": "ListDir", "path": "/x", "sort": "modified" })).unwrap();
+    assert_eq!(
+        bare,
+        Request::ListDir {
+            path: "/x".into(),
+            offset: 0,
+            limit: None,
+            stream: false,
+            options: ListOptions { sort: SortKey::Modified, ..Default::default() }
+        }
+    );
+}
+
+#[test]
+fn responses_keep_their_wire_format() {
+    assert_wire(
+        Reply {
+            id: Some(1),
+            response: Response::Ok { data: Some(ResponseData::DirectoryListing { entries: vec![entry("/a.txt")], total: 40 }) }
+        },
+        json!({
+            "request_id": 1, "status": "ok",
+            "data": {
+                "type": "DirectoryListing",
+                "entries": [{
+                    "id": "00000000000000000000000000000007", "path": "/a.txt", "size": 12, "is_dir": false,
+                    "kind": "file", "modified": 1_700_000_000, "has_thumbnail": true
+                }],
+                "total": 40
+            }
+        })
+    );
+
+    assert_wire(
+        Reply { id: Some(1), response: Response::Ok { data: Some(ResponseData::ListingStart { total: 40 }) } },
+        json!({ "request_id": 1, "status": "ok", "data": { "type": "ListingStart", "total": 40 } })
+    );
+    assert_wire(
+        Reply { id: Some(1), response: Response::Ok { data: Some(ResponseData::ListingEnd) } },
+        json!({ "request_id": 1, "status": "ok", "data": { "type": "ListingEnd" } })
+    );
+
+    assert_wire(
+        Reply { id: Some(2), response: Response::Error { message: "nope".into() } },
+        json!({ "request_id": 2, "status": "error", "message": "nope" })
+    );
+
+    assert_wire(
+        Reply {
+            id: None,
+            response: Response::Event { event: Event::Renamed { id: "7".into(), from: "/a".into(), to: "/b".into() } }
+        },
+        json!({ "status": "event", "event": { "kind": "renamed", "id": "7", "from": "/a", "to": "/b" } })
+    );
+}
+
+#[test]
+fn handshake_keeps_its_wire_format() {
+    assert_wire(
+        Handshake {
+            protocol: 2,
+            min_protocol: 2,
+            engine: "lunio-daemon".into(),
+            capabilities: vec!["binary".into()],
+            challenge: None
+        },
+        json!({ "protocol": 2, "min_protocol": 2, "engine": "lunio-daemon", "capabilities": ["binary"] })
+    );
+
+    assert_wire(
+        ClientHello::Hello {
+            min_protocol: 2,
+            max_protocol: 2,
+            capabilities: vec!["events".into()],
+            proof: Some("ab".into())
+        },
+        json!({ "type": "Hello", "min_protocol": 2, "max_protocol": 2, "capabilities": ["events"], "proof": "ab" })
+    );
+}
+
+#[test]
+fn binary_replies_survive_framing() {
+    let reply = Reply {
+        id: Some(9),
+        response: Response::Binary {
+            data: ResponseData::Thumbnails {
+                ready: vec![ThumbnailSlice { id: "a".into(), len: 3 }],
+                pending: vec!["b".into()],
+                missing: Vec::new()
+            },
+            bytes: vec![1, 2, 3]
+        }
+    };
+
+    let plain = Reply { id: Some(10), response: Response::Ok { data: Some(ResponseData::Ack) } };
+
+    for encoding in [Encoding::Json, Encoding::MessagePack] {
+        let frame = Frame::reply(reply.clone(), encoding).unwrap();
+        assert!(frame.is_binary());
+        assert_eq!(frame.request_id(encoding), Some(9));
+        assert_eq!(frame.header & !BINARY_FRAME, frame.payload.len() as u32);
+        assert_eq!(frame.into_reply(encoding).unwrap(), reply);
+
+        let frame = Frame::reply(plain.clone(), encoding).unwrap();
+        assert!(!frame.is_binary());
+        assert_eq!(frame.into_reply(encoding).unwrap(), plain);
+    }
+
+    let cut = Frame { header: 2 | BINARY_FRAME, payload: vec![0, 0] };
+    assert!(matches!(cut.clone().into_reply(Encoding::Json), Err(CodecError::Truncated)));
+
+    // Enough of a reply that does not decode to tell who it was for.
+    let garbled = Frame::json(&json!({ "request_id": 4, "status": "ok", "data": { "type": "NoSuchData" } })).unwrap();
+    assert!(garbled.clone().into_reply(Encoding::Json).is_err());
+    assert_eq!(garbled.request_id(Encoding::Json), Some(4));
+    assert_eq!(cut.request_id(Encoding::Json), None);
+}
+
+/// The bytes of a thumbnail frame, as another implementation would have to
+/// write them: the length prefix with its top bit set, the length of the
+/// JSON part, the JSON part, then the image.
+#[tokio::test]
+async fn thumbnail_frames_have_a_fixed_layout() {
+    let reply = Reply {
+        id: Some(3),
+        response: Response::Binary { data: ResponseData::Thumbnail { id: "ab".into() }, bytes: vec![0xff, 0, 7] }
+    };
+    let head = br#"{"request_id":3,"status":"ok","data":{"type":"Thumbnail","id":"ab"}}"#;
+
+    let mut wire = Vec::new();
+    write_frame(&mut wire, &Frame::reply(reply.clone(), Encoding::Json).unwrap()).await.unwrap();
+
+    let mut expected = Vec::new();
+    expected.extend_from_slice(&((4 + head.len() + 3) as u32 | BINARY_FRAME).to_be_bytes());
+    expected.extend_from_slice(&(head.len() as u32).to_be_bytes());
+    expected.extend_from_slice(head);
+    expected.extend_from_slice(&[0xff, 0, 7]);
+    assert_eq!(wire, expected);
+
+    let read = read_frame(&mut &wire[..], 1024).await.unwrap();
+    assert!(read.is_binary());
+    assert_eq!(read.into_reply(Encoding::Json).unwrap(), reply);
+}
+
+#[test]
+fn message_pack_carries_the_same_messages() {
+    assert_eq!(Encoding::negotiated(&["binary".into()]), Encoding::Json);
+    assert_eq!(Encoding::negotiated(&[CAP_MSGPACK.into()]), Encoding::MessagePack);
+
+    let requests = [
+        Envelope { id: Some(1), request: Request::Search { query: "q".into(), limit: None, mode: SearchMode::Query } },
+        Envelope {
+            id: Some(2),
+            request: Request::AddRoot {
+                path: "/media".into(),
+                options: RootOptions { exclude: vec!["*.tmp".into()], max_depth: Some(3), ..Default::default() }
+            }
+        },
+        Envelope {
+            id: Some(3),
+            request: Request::ListDir {
+                path: "/big".into(),
+                offset: 0,
+                limit: Some(50),
+                stream: false,
+                options: ListOptions { sort: SortKey::Kind, folders_first: false, ..Default::default() }
+            }
+        },
+        Envelope { id: None, request: Request::Shutdown }
+    ];
+    for request in requests {
+        let frame = Frame::encode(&request, Encoding::MessagePack).unwrap();
+        assert_eq!(frame.decode::<Envelope>(Encoding::MessagePack).unwrap(), request);
+    }
+
+    let mut linked = entry("/link");
+    linked.link_target = Some("/gone".into());
+    linked.link_broken = true;
+    linked.matches = vec![(1, 3)];
+
+    let replies = [
+        Reply {
+            id: Some(3),
+            response: Response::Ok { data: Some(ResponseData::SearchResults { entries: vec![entry("/a"), linked] }) }
+        },
+        Reply { id: None, response: Response::Event { event: Event::Created { entry: entry("/b") } } },
+        Reply { id: Some(4), response: Response::Error { message: "nope".into() } }
+    ];
+    for reply in replies {
+        let frame = Frame::reply(reply.clone(), Encoding::MessagePack).unwrap();
+        assert_eq!(frame.into_reply(Encoding::MessagePack).unwrap(), reply);
+    }
+
+    let listing = Reply {
+        id: Some(5),
+        response: Response::Ok {
+            data: Some(ResponseData::DirectoryListing {
+                entries: (0..1000).map(|i| entry(&format!("/big/{i}.jpg"))).collect(),
+                total: 1000
+            })
+        }
+    };
+    let json = Frame::reply(listing.clone(), Encoding::Json).unwrap();
+    let packed = Frame::reply(listing, Encoding::MessagePack).unwrap();
+    assert!(packed.payload.len() < json.payload.len());
+}
+
+#[tokio::test]
+async fn frames_round_trip_over_a_stream() {
+    let (mut a, mut b) = tokio::io::duplex(64);
+    let frame = Frame::json(&Envelope { id: Some(1), request: Request::Shutdown }).unwrap();
+
+    write_frame(&mut a, &frame).await.unwrap();
+    let read = read_frame(&mut b, 1024).await.unwrap();
+    assert_eq!(read, frame);
+    assert_eq!(read.parse::<Envelope>().unwrap().request, Request::Shutdown);
+
+    write_frame(&mut a, &frame).await.unwrap();
+    assert!(matches!(read_frame(&mut b, 4).await, Err(CodecError::TooLarge(_))));
+}